@@ -1,34 +1,51 @@
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, watch};
+use tokio::task::JoinSet;
+use tokio::time::{Duration, Instant, interval_at};
 use tokio_tungstenite::tungstenite::Message;
 use tokio_tungstenite::accept_async;
 use std::sync::Arc;
 use std::path::PathBuf;
 use std::fs;
+use std::fmt;
 use std::env;
 use futures_util::{SinkExt, StreamExt};
 
 // TODO: funny import, there's probably better practices
 mod simulation; 
-use crate::simulation::{simulation_loop, initialize_registry};
-use crate::simulation::config::SimulationConfig;
+use crate::simulation::{simulation_loop, initialize_registry, LogLevel};
+use crate::simulation::config::{SimulationConfig, KeepaliveConfig, ConfigError, SerializerType};
+use crate::simulation::transport::region::{ClientMessage, Region};
+use crate::simulation::transport::websocket::{FrameAssembler, frame_chunks};
 
 #[tokio::main]
 async fn main() {
     // Initialize registry with default components
     initialize_registry();
     
-    // Check for config path from command line
+    // Check for config path and log level from command line
     // BUG: fix useless directory
-    let config_path = std::env::args()
-        .nth(1)
-        .or_else(|| std::env::var("EUSOCIETY_CONFIG").ok())
+    let args = parse_args(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        eprintln!("Usage: eusociety [--config <path> | <path>] [--log-level <error|warn|info|debug>]");
+        std::process::exit(2);
+    });
+    let config_path = args.config_path
+        .or_else(|| env::var("EUSOCIETY_CONFIG").ok())
         .unwrap_or_else(|| "config/default.json".to_string());
         
-    let config = load_config(&config_path).unwrap_or_else(|e| {
-        eprintln!("Failed to load config: {}. Using defaults.", e);
-        SimulationConfig::default()
-    });
+    // A missing file falls back to the defaults, but a file that is there and wrong must not be ignored
+    let config = match load_config(&config_path) {
+        Ok(config) => config,
+        Err(LoadConfigError::Read(e)) => {
+            eprintln!("Failed to load config: {}. Using defaults.", e);
+            SimulationConfig::default()
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
     
     // Log which config we're using
     println!("Using configuration from: {}", config_path);
@@ -41,21 +58,69 @@ async fn main() {
     let (tx, _) = broadcast::channel(16);
     let tx_clone = tx.clone();
 
+    // Flipped to true on Ctrl-C so the simulation and connections can wind down
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let simulation_shutdown = shutdown_rx.clone();
+    let connection_options = ConnectionOptions {
+        keepalive: config.keepalive.clone(),
+        max_chunk_size: config.max_chunk_size,
+        serializer: config.serializer.clone(),
+    };
+
     // Start simulation in background
-    tokio::spawn(async move {
-        simulation_loop(tx_clone, config).await;
+    let mut simulation = tokio::spawn(async move {
+        simulation_loop(tx_clone, config, simulation_shutdown, args.log_level).await;
     });
 
-    // Accept WebSocket connections
-    while let Ok((stream, _)) = listener.accept().await {
-        let tx = tx.clone();
-        tokio::spawn(async move {
-            handle_connection(stream, tx).await;
-        });
+    // Accept WebSocket connections until the simulation finishes
+    let mut connections = JoinSet::new();
+    let accept_loop = async {
+        while let Ok((stream, _)) = listener.accept().await {
+            // Reap connections that have already closed
+            while connections.try_join_next().is_some() {}
+
+            let tx = tx.clone();
+            let shutdown = shutdown_rx.clone();
+            let options = connection_options.clone();
+            connections.spawn(async move {
+                handle_connection(stream, tx, shutdown, options).await;
+            });
+        }
+    };
+
+    tokio::select! {
+        _ = &mut simulation => println!("Simulation finished, shutting down"),
+        _ = accept_loop => {},
+        _ = tokio::signal::ctrl_c() => {
+            println!("Received Ctrl-C, shutting down");
+            let _ = shutdown_tx.send(true);
+            let _ = simulation.await;
+        }
     }
+
+    // Give clients a chance to receive their close frames
+    let _ = shutdown_tx.send(true);
+    let _ = tokio::time::timeout(Duration::from_secs(2), async {
+        while connections.join_next().await.is_some() {}
+    }).await;
+}
+
+// Per-connection settings taken from the simulation config
+#[derive(Debug, Clone)]
+struct ConnectionOptions {
+    keepalive: Option<KeepaliveConfig>,
+    // Used to re-chunk frames filtered down to a client's region
+    max_chunk_size: usize,
+    serializer: SerializerType,
 }
 
-async fn handle_connection(stream: TcpStream, tx: broadcast::Sender<Vec<u8>>) {
+async fn handle_connection(
+    stream: TcpStream,
+    tx: broadcast::Sender<Vec<u8>>,
+    mut shutdown: watch::Receiver<bool>,
+    options: ConnectionOptions,
+) {
+    let keepalive = options.keepalive.as_ref();
     let addr = stream.peer_addr().expect("Connected stream should have an address");
     println!("New WebSocket connection: {}", addr);
 
@@ -63,19 +128,152 @@ async fn handle_connection(stream: TcpStream, tx: broadcast::Sender<Vec<u8>>) {
         .await
         .expect("Failed to accept WebSocket connection");
 
-    let (mut ws_sender, _) = ws_stream.split();
+    let (mut ws_sender, mut ws_receiver) = ws_stream.split();
     let mut rx = tx.subscribe();
 
-    while let Ok(msg) = rx.recv().await {
-        if ws_sender.send(Message::Binary(msg)).await.is_err() {
-            break;
+    // Without keepalive the timer still exists but its branch is never polled.
+    // A zero interval is rejected by SimulationConfig::validate.
+    let ping_period = Duration::from_secs(keepalive.map_or(1, |k| k.ping_interval_secs));
+    let mut ping_timer = interval_at(Instant::now() + ping_period, ping_period);
+    let mut last_seen = Instant::now();
+    
+    // Set by the client; while set, frames are reassembled, filtered and re-chunked for this client only
+    let mut region: Option<Region> = None;
+    let mut assembler = FrameAssembler::default();
+
+    'connection: loop {
+        tokio::select! {
+            msg = rx.recv() => {
+                let Ok(msg) = msg else { break };
+                let messages = match &region {
+                    None => vec![msg],
+                    Some(region) => match assembler.push(&msg) {
+                        Some((sequence, frame)) => {
+                            let filtered = region.filter(&frame, &options.serializer);
+                            frame_chunks(sequence, &filtered, options.max_chunk_size).unwrap_or_default()
+                        }
+                        None => continue,
+                    },
+                };
+                for message in messages {
+                    if ws_sender.send(Message::Binary(message)).await.is_err() {
+                        break 'connection;
+                    }
+                }
+            }
+            incoming = ws_receiver.next() => {
+                match incoming {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(message)) => {
+                        // Any frame, pongs included, shows the client is still there
+                        last_seen = Instant::now();
+                        if let Message::Text(text) = message {
+                            match serde_json::from_str::<ClientMessage>(&text) {
+                                Ok(ClientMessage { region: Some(new_region) }) if !new_region.is_valid() => {
+                                    eprintln!("Ignoring invalid region from {}: {:?}", addr, new_region);
+                                }
+                                Ok(message) => {
+                                    region = message.region;
+                                    assembler = FrameAssembler::default();
+                                }
+                                Err(e) => eprintln!("Ignoring unrecognised message from {}: {}", addr, e),
+                            }
+                        }
+                    }
+                }
+            }
+            _ = ping_timer.tick(), if keepalive.is_some() => {
+                let idle = last_seen.elapsed();
+                if keepalive.is_some_and(|k| is_idle(idle, k)) {
+                    println!("WebSocket connection idle for {}s, dropping: {}", idle.as_secs(), addr);
+                    let _ = ws_sender.send(Message::Close(None)).await;
+                    break;
+                }
+                if ws_sender.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+            }
+            _ = shutdown.changed() => {
+                let _ = ws_sender.send(Message::Close(None)).await;
+                break;
+            }
         }
     }
     
     println!("WebSocket connection closed: {}", addr);
 }
 
-fn load_config(path_str: &str) -> Result<SimulationConfig, Box<dyn std::error::Error>> {
+// Whether a client that hasn't sent anything for `idle` should be dropped
+fn is_idle(idle: Duration, keepalive: &KeepaliveConfig) -> bool {
+    idle >= Duration::from_secs(keepalive.idle_timeout_secs)
+}
+
+// Options given on the command line
+#[derive(Debug, PartialEq)]
+struct CliArgs {
+    config_path: Option<String>,
+    log_level: LogLevel,
+}
+
+// Accepts `--config <path>` or a bare positional path, and `--log-level <level>`.
+// Flags also take `--flag=value`; anything else is an error.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<CliArgs, String> {
+    let mut config_flag = None;
+    let mut positional = None;
+    let mut log_level = None;
+    
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if positional.is_some() {
+                return Err(format!("Unexpected argument: {}", arg));
+            }
+            positional = Some(arg);
+            continue;
+        }
+        
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let target = match flag.as_str() {
+            "--config" => &mut config_flag,
+            "--log-level" => &mut log_level,
+            _ => return Err(format!("Unknown argument: {}", flag)),
+        };
+        if target.is_some() {
+            return Err(format!("{} given more than once", flag));
+        }
+        match inline_value.or_else(|| args.next()) {
+            Some(value) if !value.is_empty() && !value.starts_with("--") => *target = Some(value),
+            _ => return Err(format!("{} requires a value", flag)),
+        }
+    }
+    
+    Ok(CliArgs {
+        config_path: config_flag.or(positional),
+        log_level: log_level.map_or(Ok(LogLevel::default()), |level| level.parse())?,
+    })
+}
+
+// Why load_config failed; only Read falls back to the default config
+#[derive(Debug)]
+enum LoadConfigError {
+    Read(std::io::Error),
+    Parse(serde_json::Error),
+    Invalid(ConfigError),
+}
+
+impl fmt::Display for LoadConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadConfigError::Read(e) => write!(f, "{}", e),
+            LoadConfigError::Parse(e) => write!(f, "Failed to parse config: {}", e),
+            LoadConfigError::Invalid(e) => write!(f, "Invalid config: {}", e),
+        }
+    }
+}
+
+fn load_config(path_str: &str) -> Result<SimulationConfig, LoadConfigError> {
     let path = std::path::PathBuf::from(path_str);
     
     // Try to load the specified config
@@ -87,15 +285,250 @@ fn load_config(path_str: &str) -> Result<SimulationConfig, Box<dyn std::error::E
             // If specified config doesn't exist, try default
             if path_str != "config/default.json" {
                 println!("Falling back to default config");
-                fs::read_to_string("config/default.json")?
+                fs::read_to_string("config/default.json").map_err(LoadConfigError::Read)?
             } else {
-                return Err(e.into());
+                return Err(LoadConfigError::Read(e));
             }
         }
     };
     
     // Parse the config JSON
-    let config: SimulationConfig = serde_json::from_str(&config_data)?;
+    let config: SimulationConfig = serde_json::from_str(&config_data).map_err(LoadConfigError::Parse)?;
+    config.validate().map_err(LoadConfigError::Invalid)?;
     
     Ok(config)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::entity::Entity;
+    use crate::simulation::entity::particle::Particle;
+    use crate::simulation::transport::{Serializer, Transport};
+    use crate::simulation::transport::websocket::{BinarySerializer, WebSocketTransport};
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use tokio::io::AsyncReadExt;
+    
+    fn args(values: &[&str]) -> impl Iterator<Item = String> {
+        values.iter().map(|value| value.to_string()).collect::<Vec<_>>().into_iter()
+    }
+    
+    fn config_path(values: &[&str]) -> Result<Option<String>, String> {
+        parse_args(args(values)).map(|cli| cli.config_path)
+    }
+    
+    #[test]
+    fn config_path_forms() {
+        assert_eq!(config_path(&["--config", "a.json"]), Ok(Some("a.json".to_string())));
+        assert_eq!(config_path(&["--config=b.json"]), Ok(Some("b.json".to_string())));
+        assert_eq!(config_path(&["c.json"]), Ok(Some("c.json".to_string())));
+        assert_eq!(config_path(&[]), Ok(None));
+    }
+    
+    #[test]
+    fn config_flag_wins_over_positional_path() {
+        assert_eq!(config_path(&["c.json", "--config", "a.json"]), Ok(Some("a.json".to_string())));
+    }
+    
+    #[test]
+    fn config_flag_without_a_path_is_an_error() {
+        assert!(config_path(&["--config"]).is_err());
+        assert!(config_path(&["--config="]).is_err());
+        assert!(config_path(&["--config", "--log-level", "info"]).is_err());
+    }
+    
+    #[test]
+    fn log_level_is_parsed() {
+        assert_eq!(parse_args(args(&[])).map(|cli| cli.log_level), Ok(LogLevel::Info));
+        assert_eq!(
+            parse_args(args(&["--log-level", "warn", "--config", "a.json"])),
+            Ok(CliArgs { config_path: Some("a.json".to_string()), log_level: LogLevel::Warn }),
+        );
+        assert_eq!(parse_args(args(&["--log-level=debug"])).map(|cli| cli.log_level), Ok(LogLevel::Debug));
+        assert!(parse_args(args(&["--log-level", "loud"])).is_err());
+    }
+    
+    #[test]
+    fn unknown_or_extra_arguments_are_rejected() {
+        assert!(parse_args(args(&["--verbose"])).is_err());
+        assert!(parse_args(args(&["--config", "a.json", "--bogus"])).is_err());
+        assert!(parse_args(args(&["--config", "a.json", "--config", "b.json"])).is_err());
+        assert!(parse_args(args(&["a.json", "b.json"])).is_err());
+    }
+    
+    // Serve one WebSocket connection on a local port and connect a client to it
+    async fn serve_connection(options: ConnectionOptions) -> (
+        tokio::task::JoinHandle<()>,
+        tokio_tungstenite::WebSocketStream<TcpStream>,
+        broadcast::Sender<Vec<u8>>,
+        watch::Sender<bool>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, _) = broadcast::channel(16);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        
+        let server_tx = tx.clone();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            handle_connection(stream, server_tx, shutdown_rx, options).await;
+        });
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (client, _) = tokio_tungstenite::client_async(format!("ws://{}", addr), stream).await.unwrap();
+        (server, client, tx, shutdown_tx)
+    }
+    
+    async fn keepalive_connection() -> (
+        tokio::task::JoinHandle<()>,
+        tokio_tungstenite::WebSocketStream<TcpStream>,
+        watch::Sender<bool>,
+    ) {
+        let options = ConnectionOptions {
+            keepalive: Some(KeepaliveConfig { ping_interval_secs: 3, idle_timeout_secs: 10 }),
+            max_chunk_size: 65536,
+            serializer: SerializerType::Binary,
+        };
+        let (server, client, _, shutdown_tx) = serve_connection(options).await;
+        (server, client, shutdown_tx)
+    }
+    
+    #[tokio::test]
+    async fn client_region_only_receives_entities_inside() {
+        // Small chunks so frames are split both before and after filtering
+        let options = ConnectionOptions { keepalive: None, max_chunk_size: 20, serializer: SerializerType::Binary };
+        let (server, mut client, tx, shutdown_tx) = serve_connection(options).await;
+        
+        client.send(Message::Text(r#"{"region": {"min_x": 0, "min_y": 0, "max_x": 100, "max_y": 100}}"#.to_string())).await.unwrap();
+        // Messages are handled in order, so once the ping is answered the region is in place
+        client.send(Message::Ping(vec![1])).await.unwrap();
+        match client.next().await {
+            Some(Ok(Message::Pong(payload))) => assert_eq!(payload, vec![1]),
+            other => panic!("expected a pong, got {:?}", other),
+        }
+        
+        let mut rng = StdRng::seed_from_u64(0);
+        let entities: Vec<Box<dyn Entity + Send + Sync>> = [(10.0, 10.0), (150.0, 10.0), (90.0, 60.0), (50.0, 300.0)].iter().enumerate()
+            .map(|(id, &(x, y))| Box::new(Particle::new(id, x, y, &mut rng)) as Box<dyn Entity + Send + Sync>)
+            .collect();
+        WebSocketTransport::new(tx, 20).send_state(&BinarySerializer.serialize_entities(&entities)).unwrap();
+        
+        let mut assembler = FrameAssembler::default();
+        let frame = loop {
+            match client.next().await {
+                Some(Ok(Message::Binary(chunk))) => {
+                    if let Some((_, frame)) = assembler.push(&chunk) {
+                        break frame;
+                    }
+                }
+                other => panic!("expected a state chunk, got {:?}", other),
+            }
+        };
+        let ids: Vec<u32> = frame.chunks_exact(13)
+            .map(|record| u32::from_le_bytes(record[1..5].try_into().unwrap()))
+            .collect();
+        assert_eq!(ids, vec![0, 2]);
+        
+        shutdown_tx.send(true).unwrap();
+        server.await.unwrap();
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn unresponsive_client_is_dropped_after_the_idle_timeout() {
+        let (server, mut client, _shutdown_tx) = keepalive_connection().await;
+        let start = Instant::now();
+        
+        // The client never reads, so it never answers the server's pings
+        server.await.unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(10) && elapsed < Duration::from_secs(13), "dropped after {:?}", elapsed);
+        
+        // Read the raw frames, since a WebSocket client would try to answer the pings on a closed socket.
+        // Pings at 3s, 6s and 9s went unanswered, then the server sent an empty close frame at 12s.
+        let mut frames = Vec::new();
+        client.get_mut().read_to_end(&mut frames).await.unwrap();
+        let ping = [0x89, 0x00];
+        let close = [0x88, 0x00];
+        assert_eq!(frames, [ping, ping, ping, close].concat());
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn responsive_client_is_kept() {
+        let (server, mut client, shutdown_tx) = keepalive_connection().await;
+        
+        // Reading makes the client answer pings with pongs
+        let reader = tokio::spawn(async move {
+            while let Some(Ok(message)) = client.next().await {
+                if message.is_close() {
+                    return true;
+                }
+            }
+            false
+        });
+        
+        tokio::time::sleep(Duration::from_secs(60)).await;
+        assert!(!server.is_finished());
+        
+        shutdown_tx.send(true).unwrap();
+        server.await.unwrap();
+        assert!(reader.await.unwrap(), "client should receive a close frame on shutdown");
+    }
+    
+    // Write a config file to a unique temp path and load it
+    fn load_json(name: &str, json: &serde_json::Value) -> Result<SimulationConfig, LoadConfigError> {
+        let path = env::temp_dir().join(format!("eusociety-{}-{}.json", std::process::id(), name));
+        fs::write(&path, json.to_string()).unwrap();
+        let result = load_config(path.to_str().unwrap());
+        let _ = fs::remove_file(&path);
+        result
+    }
+    
+    #[test]
+    fn zero_values_fail_to_load() {
+        let cases: [(&str, &[&str], serde_json::Value); 7] = [
+            ("tick_rate_ms", &["tick_rate_ms"], 0.into()),
+            ("broadcast_rate", &["broadcast_rate"], 0.into()),
+            ("batch_size", &["batch_size"], 0.into()),
+            ("max_chunk_size", &["max_chunk_size"], 0.into()),
+            ("fixed_timestep_ms", &["fixed_timestep_ms"], 0.into()),
+            ("world.width", &["world", "width"], 0.into()),
+            ("world.height", &["world", "height"], (-10.0).into()),
+        ];
+        
+        for (name, path, value) in cases {
+            let mut json = serde_json::to_value(SimulationConfig::default()).unwrap();
+            let (key, parents) = path.split_last().unwrap();
+            let object = parents.iter().fold(&mut json, |json, parent| &mut json[*parent]);
+            object[*key] = value;
+            
+            match load_json(name, &json) {
+                Err(LoadConfigError::Invalid(e)) => assert_eq!(e, ConfigError::MustBeNonZero(name)),
+                other => panic!("{}: expected a validation error, got {:?}", name, other.map(|_| ())),
+            }
+        }
+    }
+    
+    #[test]
+    fn valid_config_loads() {
+        let json = serde_json::to_value(SimulationConfig::default()).unwrap();
+        assert!(load_json("valid", &json).is_ok());
+    }
+    
+    #[test]
+    fn missing_file_is_a_read_error() {
+        assert!(matches!(load_config("/nonexistent/eusociety.json"), Err(LoadConfigError::Read(_))));
+    }
+    
+    #[test]
+    fn misspelled_key_fails_to_load() {
+        let mut json = serde_json::to_value(SimulationConfig::default()).unwrap();
+        let entity_config = json["entity_configs"][0].as_object_mut().unwrap();
+        let count = entity_config.remove("count").unwrap();
+        entity_config.insert("cuont".to_string(), count);
+        
+        match load_json("misspelled", &json) {
+            Err(LoadConfigError::Parse(e)) => assert!(e.to_string().contains("cuont"), "{}", e),
+            other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -1,14 +1,68 @@
 use serde::{Serialize, Deserialize};
 use std::collections::HashMap;
+use std::fmt;
+use crate::simulation::registry::REGISTRY;
 
 // This module contains the configuration structs for the simulation.
+// Unknown keys are rejected so typos fail loudly. Per-type settings go in the `properties` maps, whose keys
+// are checked against what each factory declares it reads, plus `allowed_extra_properties`.
 
 // The WorldConfig struct contains the configuration for the world boundaries.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct WorldConfig {
     pub width: f32,
     pub height: f32,
     pub boundary_mode: BoundaryMode,
+    // Fraction of the normal velocity kept when bouncing off a wall (0..=1); only used with Bounce
+    #[serde(default = "default_restitution")]
+    pub restitution: f32,
+    // Constant acceleration applied to every particle, e.g. gravity; zero by default
+    #[serde(default)]
+    pub gravity: GlobalForce,
+}
+
+// Acceleration in world units per second squared
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GlobalForce {
+    pub x: f32,
+    pub y: f32,
+}
+
+// Axis-aligned rectangle in world coordinates; edges are inclusive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+impl Rect {
+    pub fn width(&self) -> f32 {
+        self.max_x - self.min_x
+    }
+    
+    pub fn height(&self) -> f32 {
+        self.max_y - self.min_y
+    }
+    
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+}
+
+impl WorldConfig {
+    // The canonical world rectangle. The origin is the top-left corner and y grows downwards,
+    // matching the frontend's canvas, so the world spans (0, 0) to (width, height).
+    pub fn bounds(&self) -> Rect {
+        Rect { min_x: 0.0, min_y: 0.0, max_x: self.width, max_y: self.height }
+    }
+}
+
+fn default_restitution() -> f32 {
+    1.0
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,25 +72,81 @@ pub enum BoundaryMode {
     Kill,
 }
 
+// Wire format for entity state. The WebGL frontend only decodes Binary; Json is for debugging.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub enum SerializerType {
+    #[default]
+    Binary,
+    Json,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct SimulationConfig {
     pub world: WorldConfig,
     pub entity_configs: Vec<EntityConfig>,
     pub field_configs: Vec<FieldConfig>,
+    #[serde(default)]
+    pub emitter_configs: Vec<EmitterConfig>,
     pub tick_rate_ms: u64,
     pub broadcast_rate: u32,
     pub max_chunk_size: usize, 
     pub batch_size: usize,  
+    #[serde(default)]
+    pub serializer: SerializerType,
+    // When set, the engine advances in fixed steps of this size regardless of tick timing
+    #[serde(default)]
+    pub fixed_timestep_ms: Option<u64>,
+    // WebSocket ping/idle-timeout settings; disabled when absent
+    #[serde(default)]
+    pub keepalive: Option<KeepaliveConfig>,
+    // Optional limits after which the simulation loop exits.
+    // max_frames counts engine updates, so with fixed_timestep_ms it counts fixed steps rather than ticks.
+    #[serde(default)]
+    pub max_frames: Option<u32>,
+    #[serde(default)]
+    pub max_duration_secs: Option<f64>,
+    // Seed for all simulation randomness; with the same seed and config, runs on the same build are identical
+    // bit for bit, interactions included. Unseeded runs draw a fresh seed from the OS.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    // Property keys accepted for any entity or field type even though no factory reads them,
+    // e.g. settings for a newer build sharing the same config file
+    #[serde(default)]
+    pub allowed_extra_properties: Vec<String>,
 }
 
+// Clients are pinged every ping_interval_secs and dropped if nothing is heard from them for idle_timeout_secs
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeepaliveConfig {
+    pub ping_interval_secs: u64,
+    pub idle_timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct EntityConfig {
     pub entity_type: String,
     pub count: usize,
     pub properties: HashMap<String, serde_json::Value>,
 }
 
+// An emitter spawns entities of one type at a fixed position over time.
+// Fractional spawns carry over between ticks, so the spawn count only depends on elapsed time.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmitterConfig {
+    pub entity_type: String,
+    pub rate_per_sec: f32,
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub properties: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct FieldConfig {
     pub field_type: String,
     pub resolution: usize,
@@ -51,6 +161,8 @@ impl Default for WorldConfig {
             width: 6000.0,
             height: 6000.0,
             boundary_mode: BoundaryMode::Bounce,
+            restitution: default_restitution(),
+            gravity: GlobalForce::default(),
         }
     }
 }
@@ -67,10 +179,330 @@ impl Default for SimulationConfig {
                 }
             ],
             field_configs: vec![],
+            emitter_configs: vec![],
             tick_rate_ms: 10,
             broadcast_rate: 1,
             max_chunk_size: 65536,
             batch_size: 4096, 
+            serializer: SerializerType::Binary,
+            fixed_timestep_ms: None,
+            keepalive: None,
+            max_frames: None,
+            max_duration_secs: None,
+            seed: None,
+            allowed_extra_properties: vec![],
+        }
+    }
+}
+
+// Emitters spawn rate_per_sec * dt entities per tick, so keep that from growing without bound
+pub const MAX_EMITTER_RATE_PER_SEC: u32 = 100_000;
+
+// Errors for config values the simulation can't run with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    // Property keys that neither the type's factory nor allowed_extra_properties know about
+    UnknownProperties {
+        location: String,
+        type_name: String,
+        keys: Vec<String>,
+        known: &'static [&'static str],
+    },
+    MustBeNonZero(&'static str),
+    MustBeInUnitRange(&'static str),
+    MustBeFiniteNonNegative(&'static str),
+    MustBeFinite(&'static str),
+    ExceedsMaximum(&'static str, u32),
+    MustExceed(&'static str, &'static str),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::UnknownProperties { location, type_name, keys, known } => write!(
+                f,
+                "unknown properties {:?} for '{}' in {}; known properties are {:?} (add extra keys to allowed_extra_properties)",
+                keys, type_name, location, known,
+            ),
+            ConfigError::MustBeNonZero(name) => write!(f, "config value '{}' must be greater than zero", name),
+            ConfigError::MustBeInUnitRange(name) => write!(f, "config value '{}' must be between 0 and 1", name),
+            ConfigError::MustBeFiniteNonNegative(name) => write!(f, "config value '{}' must be a finite number of zero or more", name),
+            ConfigError::MustBeFinite(name) => write!(f, "config value '{}' must be a finite number", name),
+            ConfigError::ExceedsMaximum(name, max) => write!(f, "config value '{}' must not exceed {}", name, max),
+            ConfigError::MustExceed(name, other) => write!(f, "config value '{}' must be greater than '{}'", name, other),
         }
     }
-}
\ No newline at end of file
+}
+
+impl std::error::Error for ConfigError {}
+
+impl SimulationConfig {
+    // Shorthand for world.bounds(); boundary handling, spawning and culling all go through this
+    pub fn world_bounds(&self) -> Rect {
+        self.world.bounds()
+    }
+    
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        // Wrapping and spawning both divide the world up, so an empty or NaN world is unusable
+        if !self.world.width.is_finite() || self.world.width <= 0.0 {
+            return Err(ConfigError::MustBeNonZero("world.width"));
+        }
+        if !self.world.height.is_finite() || self.world.height <= 0.0 {
+            return Err(ConfigError::MustBeNonZero("world.height"));
+        }
+        if !self.world.gravity.x.is_finite() || !self.world.gravity.y.is_finite() {
+            return Err(ConfigError::MustBeFinite("world.gravity"));
+        }
+        if self.tick_rate_ms == 0 {
+            return Err(ConfigError::MustBeNonZero("tick_rate_ms"));
+        }
+        if self.broadcast_rate == 0 {
+            return Err(ConfigError::MustBeNonZero("broadcast_rate"));
+        }
+        if self.batch_size == 0 {
+            return Err(ConfigError::MustBeNonZero("batch_size"));
+        }
+        if self.max_chunk_size == 0 {
+            return Err(ConfigError::MustBeNonZero("max_chunk_size"));
+        }
+        if self.fixed_timestep_ms == Some(0) {
+            return Err(ConfigError::MustBeNonZero("fixed_timestep_ms"));
+        }
+        if self.max_duration_secs.is_some_and(|secs| !secs.is_finite() || secs < 0.0) {
+            return Err(ConfigError::MustBeFiniteNonNegative("max_duration_secs"));
+        }
+        if !(0.0..=1.0).contains(&self.world.restitution) {
+            return Err(ConfigError::MustBeInUnitRange("world.restitution"));
+        }
+        if let Some(keepalive) = &self.keepalive {
+            if keepalive.ping_interval_secs == 0 {
+                return Err(ConfigError::MustBeNonZero("keepalive.ping_interval_secs"));
+            }
+            // Clients only answer pings, so a timeout shorter than the interval drops them before the first ping
+            if keepalive.idle_timeout_secs <= keepalive.ping_interval_secs {
+                return Err(ConfigError::MustExceed("keepalive.idle_timeout_secs", "keepalive.ping_interval_secs"));
+            }
+        }
+        for entity_config in &self.entity_configs {
+            check_non_negative_property(&entity_config.properties, "velocity_spread", "entity_configs.properties.velocity_spread")?;
+            check_non_negative_property(&entity_config.properties, "jitter", "entity_configs.properties.jitter")?;
+        }
+        for emitter_config in &self.emitter_configs {
+            if !is_finite_non_negative(emitter_config.rate_per_sec) {
+                return Err(ConfigError::MustBeFiniteNonNegative("emitter_configs.rate_per_sec"));
+            }
+            if emitter_config.rate_per_sec > MAX_EMITTER_RATE_PER_SEC as f32 {
+                return Err(ConfigError::ExceedsMaximum("emitter_configs.rate_per_sec", MAX_EMITTER_RATE_PER_SEC));
+            }
+            check_non_negative_property(&emitter_config.properties, "velocity_spread", "emitter_configs.properties.velocity_spread")?;
+            check_non_negative_property(&emitter_config.properties, "jitter", "emitter_configs.properties.jitter")?;
+        }
+        self.check_property_keys()
+    }
+    
+    // Types without a registered factory are left to SimulationEngine::initialize to report
+    fn check_property_keys(&self) -> Result<(), ConfigError> {
+        for (i, entity_config) in self.entity_configs.iter().enumerate() {
+            if let Some(factory) = REGISTRY.get_entity_factory(&entity_config.entity_type) {
+                let location = format!("entity_configs[{}]", i);
+                self.check_known(&entity_config.properties, factory.known_properties(), location, &entity_config.entity_type)?;
+            }
+        }
+        for (i, emitter_config) in self.emitter_configs.iter().enumerate() {
+            if let Some(factory) = REGISTRY.get_entity_factory(&emitter_config.entity_type) {
+                let location = format!("emitter_configs[{}]", i);
+                self.check_known(&emitter_config.properties, factory.known_properties(), location, &emitter_config.entity_type)?;
+            }
+        }
+        for (i, field_config) in self.field_configs.iter().enumerate() {
+            if let Some(factory) = REGISTRY.get_field_factory(&field_config.field_type) {
+                let location = format!("field_configs[{}]", i);
+                self.check_known(&field_config.properties, factory.known_properties(), location, &field_config.field_type)?;
+            }
+        }
+        Ok(())
+    }
+    
+    fn check_known(
+        &self,
+        properties: &HashMap<String, serde_json::Value>,
+        known: &'static [&'static str],
+        location: String,
+        type_name: &str,
+    ) -> Result<(), ConfigError> {
+        let mut keys: Vec<String> = properties.keys()
+            .filter(|key| !known.contains(&key.as_str()) && !self.allowed_extra_properties.contains(key))
+            .cloned()
+            .collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+        keys.sort();
+        Err(ConfigError::UnknownProperties { location, type_name: type_name.to_string(), keys, known })
+    }
+}
+
+fn is_finite_non_negative(value: f32) -> bool {
+    value.is_finite() && value >= 0.0
+}
+
+// velocity_spread and jitter are free-form properties, but they end up as sampling ranges so they have to be usable as one
+fn check_non_negative_property(properties: &HashMap<String, serde_json::Value>, key: &str, name: &'static str) -> Result<(), ConfigError> {
+    match properties.get(key).and_then(|v| v.as_f64()) {
+        Some(value) if !is_finite_non_negative(value as f32) => Err(ConfigError::MustBeFiniteNonNegative(name)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::entity::EntityFactory;
+    use crate::simulation::entity::particle::ParticleFactory;
+    use crate::simulation::field::FieldFactory;
+    use crate::simulation::field::scalar_field::ScalarFieldFactory;
+    
+    fn emitter(rate_per_sec: f32, properties: serde_json::Value) -> EmitterConfig {
+        EmitterConfig {
+            entity_type: "particle".to_string(),
+            rate_per_sec,
+            x: 0.0,
+            y: 0.0,
+            properties: serde_json::from_value(properties).unwrap(),
+        }
+    }
+    
+    fn with_emitter(emitter: EmitterConfig) -> SimulationConfig {
+        let mut config = SimulationConfig::default();
+        config.emitter_configs.push(emitter);
+        config
+    }
+    
+    #[test]
+    fn emitter_rate_must_be_finite_non_negative_and_bounded() {
+        for rate in [-1.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                with_emitter(emitter(rate, serde_json::json!({}))).validate(),
+                Err(ConfigError::MustBeFiniteNonNegative("emitter_configs.rate_per_sec")),
+            );
+        }
+        assert_eq!(
+            with_emitter(emitter(1e7, serde_json::json!({}))).validate(),
+            Err(ConfigError::ExceedsMaximum("emitter_configs.rate_per_sec", MAX_EMITTER_RATE_PER_SEC)),
+        );
+        assert_eq!(with_emitter(emitter(0.0, serde_json::json!({}))).validate(), Ok(()));
+    }
+    
+    #[test]
+    fn velocity_spread_must_fit_in_f32() {
+        for spread in [1e39, -5.0] {
+            assert_eq!(
+                with_emitter(emitter(10.0, serde_json::json!({ "velocity_spread": spread }))).validate(),
+                Err(ConfigError::MustBeFiniteNonNegative("emitter_configs.properties.velocity_spread")),
+            );
+        }
+        assert_eq!(with_emitter(emitter(10.0, serde_json::json!({ "velocity_spread": 20.0 }))).validate(), Ok(()));
+    }
+    
+    #[test]
+    fn jitter_must_fit_in_f32() {
+        for jitter in [1e39, -1.0] {
+            assert_eq!(
+                with_emitter(emitter(10.0, serde_json::json!({ "jitter": jitter }))).validate(),
+                Err(ConfigError::MustBeFiniteNonNegative("emitter_configs.properties.jitter")),
+            );
+        }
+        assert_eq!(with_emitter(emitter(10.0, serde_json::json!({ "jitter": 0.0 }))).validate(), Ok(()));
+    }
+    
+    #[test]
+    fn keepalive_timeout_must_exceed_ping_interval() {
+        let with_keepalive = |ping_interval_secs, idle_timeout_secs| SimulationConfig {
+            keepalive: Some(KeepaliveConfig { ping_interval_secs, idle_timeout_secs }),
+            ..SimulationConfig::default()
+        };
+        
+        assert_eq!(with_keepalive(0, 10).validate(), Err(ConfigError::MustBeNonZero("keepalive.ping_interval_secs")));
+        for idle_timeout_secs in [0, 5, 10] {
+            assert_eq!(
+                with_keepalive(10, idle_timeout_secs).validate(),
+                Err(ConfigError::MustExceed("keepalive.idle_timeout_secs", "keepalive.ping_interval_secs")),
+            );
+        }
+        assert_eq!(with_keepalive(10, 30).validate(), Ok(()));
+    }
+    
+    #[test]
+    fn max_duration_must_be_finite_non_negative() {
+        for secs in [-1.0, f64::NAN, f64::INFINITY] {
+            let config = SimulationConfig { max_duration_secs: Some(secs), ..SimulationConfig::default() };
+            assert_eq!(config.validate(), Err(ConfigError::MustBeFiniteNonNegative("max_duration_secs")));
+        }
+        let config = SimulationConfig { max_duration_secs: Some(2.5), ..SimulationConfig::default() };
+        assert_eq!(config.validate(), Ok(()));
+    }
+    
+    #[test]
+    fn misspelled_property_keys_are_rejected() {
+        let _ = REGISTRY.register_entity_factory("particle", Box::new(ParticleFactory));
+        let mut config = SimulationConfig::default();
+        config.entity_configs[0].properties = serde_json::from_value(serde_json::json!({
+            "max_sped": 10.0,
+            "velocity_spread": 5.0,
+            "jiter": 1.0,
+        })).unwrap();
+        
+        assert_eq!(config.validate(), Err(ConfigError::UnknownProperties {
+            location: "entity_configs[0]".to_string(),
+            type_name: "particle".to_string(),
+            keys: vec!["jiter".to_string(), "max_sped".to_string()],
+            known: ParticleFactory.known_properties(),
+        }));
+        
+        config.allowed_extra_properties = vec!["max_sped".to_string(), "jiter".to_string()];
+        assert_eq!(config.validate(), Ok(()));
+    }
+    
+    #[test]
+    fn misspelled_field_property_keys_are_rejected() {
+        let _ = REGISTRY.register_field_factory("scalar", Box::new(ScalarFieldFactory));
+        let mut config = SimulationConfig::default();
+        config.field_configs.push(FieldConfig {
+            field_type: "scalar".to_string(),
+            resolution: 10,
+            decay_rate: 0.1,
+            diffusion_rate: 0.05,
+            properties: serde_json::from_value(serde_json::json!({ "decay_rte": 0.2 })).unwrap(),
+        });
+        
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::UnknownProperties { location, keys, .. }) if location == "field_configs[0]" && keys == ["decay_rte"]
+        ));
+    }
+    
+    #[test]
+    fn rejects_non_finite_gravity() {
+        for gravity in [GlobalForce { x: f32::NAN, y: 0.0 }, GlobalForce { x: 0.0, y: f32::INFINITY }] {
+            let mut config = SimulationConfig::default();
+            config.world.gravity = gravity;
+            assert_eq!(config.validate(), Err(ConfigError::MustBeFinite("world.gravity")));
+        }
+        let mut config = SimulationConfig::default();
+        config.world.gravity = GlobalForce { x: 0.0, y: 9.8 };
+        assert_eq!(config.validate(), Ok(()));
+    }
+    
+    #[test]
+    fn world_bounds_match_the_configured_size() {
+        let mut config = SimulationConfig::default();
+        config.world.width = 800.0;
+        config.world.height = 600.0;
+        
+        let bounds = config.world_bounds();
+        assert_eq!(bounds, Rect { min_x: 0.0, min_y: 0.0, max_x: 800.0, max_y: 600.0 });
+        assert_eq!((bounds.width(), bounds.height()), (800.0, 600.0));
+        assert!(bounds.contains(0.0, 0.0) && bounds.contains(800.0, 600.0));
+        assert!(!bounds.contains(-0.1, 10.0) && !bounds.contains(10.0, 600.1));
+    }
+}
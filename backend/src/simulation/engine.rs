@@ -1,16 +1,45 @@
 use crate::simulation::entity::Entity;
 use crate::simulation::field::Field;
-use crate::simulation::config::{SimulationConfig, WorldConfig};
-use crate::simulation::registry::REGISTRY;
+use crate::simulation::config::{SimulationConfig, WorldConfig, BoundaryMode};
+use crate::simulation::registry::{REGISTRY, RegistryError};
+use crate::simulation::spatial::SpatialGrid;
+use crate::simulation::transport::Serializer;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 use rayon::prelude::*;
 use std::sync::Arc;
 use std::time::Instant;
 
+// Hands out entity ids in spawn order. Ids are only reused after reset() or once the u32 range wraps.
+#[derive(Debug, Default)]
+pub struct EntityIdAllocator {
+    next_id: u32,
+}
+
+impl EntityIdAllocator {
+    pub fn allocate(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.checked_add(1).unwrap_or_else(|| {
+            eprintln!("Entity id space exhausted, wrapping around to 0; ids are no longer unique");
+            0
+        });
+        id
+    }
+    
+    pub fn reset(&mut self) {
+        self.next_id = 0;
+    }
+}
+
 pub struct SimulationEngine {
     pub entities: Vec<Box<dyn Entity + Send + Sync>>,
     pub fields: Vec<Arc<dyn Field + Send + Sync>>,
     pub config: SimulationConfig,
     pub batch_size: usize,
+    entity_ids: EntityIdAllocator,
+    emitter_accumulators: Vec<f32>,
+    spatial_grid: SpatialGrid,
+    rng: StdRng,
 }
 
 impl SimulationEngine {
@@ -20,6 +49,10 @@ impl SimulationEngine {
             fields: Vec::new(),
             config: SimulationConfig::default(),
             batch_size,
+            entity_ids: EntityIdAllocator::default(),
+            emitter_accumulators: Vec::new(),
+            spatial_grid: SpatialGrid::new(1.0),
+            rng: StdRng::from_entropy(),
         }
     }
     
@@ -28,42 +61,78 @@ impl SimulationEngine {
         self
     }
     
-    pub fn initialize(&mut self) {
+    pub fn initialize(&mut self) -> Result<(), RegistryError> {
         // Clear existing entities and fields
         self.entities.clear();
         self.fields.clear();
+        self.rng = match self.config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        
+        // Emitters only look up their factory when they fire, so check them up front
+        for emitter_config in &self.config.emitter_configs {
+            if REGISTRY.get_entity_factory(&emitter_config.entity_type).is_none() {
+                return Err(RegistryError::UnknownEntity(emitter_config.entity_type.clone()));
+            }
+        }
+        
+        let bounds = self.config.world_bounds();
         
         // Create fields based on config
         for field_config in &self.config.field_configs {
-            if let Some(factory) = REGISTRY.get_field_factory(&field_config.field_type) {
-                let field = factory.create_field(
-                    self.config.world.width,
-                    self.config.world.height,
-                    field_config.resolution,
-                    &serde_json::to_value(&field_config.properties).unwrap_or_default(),
-                );
-                let field: Box<dyn Field + Send + Sync> = field;
-                self.fields.push(Arc::from(field));
-            }
+            let factory = REGISTRY.get_field_factory(&field_config.field_type)
+                .ok_or_else(|| RegistryError::UnknownField(field_config.field_type.clone()))?;
+            let field = factory.create_field(
+                bounds.width(),
+                bounds.height(),
+                field_config.resolution,
+                &serde_json::to_value(&field_config.properties).unwrap_or_default(),
+            );
+            let field: Box<dyn Field + Send + Sync> = field;
+            self.fields.push(Arc::from(field));
         }
         
         // Create entities based on config
-        let mut next_id = 0;
+        self.entity_ids.reset();
         for entity_config in &self.config.entity_configs {
-            if let Some(factory) = REGISTRY.get_entity_factory(&entity_config.entity_type) {
-                for _ in 0..entity_config.count {
-                    // Generate position based on entity type and count
-                    let x = rand::random::<f32>() * self.config.world.width;
-                    let y = rand::random::<f32>() * self.config.world.height;
-                    
-                    let entity = factory.create_entity(
-                        next_id,
-                        x, y,
-                        &serde_json::to_value(&entity_config.properties).unwrap_or_default(),
-                    );
-                    
+            let factory = REGISTRY.get_entity_factory(&entity_config.entity_type)
+                .ok_or_else(|| RegistryError::UnknownEntity(entity_config.entity_type.clone()))?;
+            for _ in 0..entity_config.count {
+                // Generate position based on entity type and count
+                let x = bounds.min_x + self.rng.r#gen::<f32>() * bounds.width();
+                let y = bounds.min_y + self.rng.r#gen::<f32>() * bounds.height();
+                
+                let entity = factory.create_entity(
+                    self.entity_ids.allocate(),
+                    x, y,
+                    &serde_json::to_value(&entity_config.properties).unwrap_or_default(),
+                    &mut self.rng,
+                );
+                
+                self.entities.push(entity);
+            }
+        }
+        
+        self.emitter_accumulators = vec![0.0; self.config.emitter_configs.len()];
+        Ok(())
+    }
+    
+    // Spawn entities from configured emitters, carrying fractional spawns over to the next tick
+    fn run_emitters(&mut self, dt: f32) {
+        for (emitter_config, accumulator) in self.config.emitter_configs.iter().zip(&mut self.emitter_accumulators) {
+            *accumulator += emitter_config.rate_per_sec * dt;
+            let spawn_count = accumulator.floor();
+            if spawn_count < 1.0 {
+                continue;
+            }
+            *accumulator -= spawn_count;
+            
+            if let Some(factory) = REGISTRY.get_entity_factory(&emitter_config.entity_type) {
+                let properties = serde_json::to_value(&emitter_config.properties).unwrap_or_default();
+                for _ in 0..spawn_count as usize {
+                    let entity = factory.create_entity(self.entity_ids.allocate(), emitter_config.x, emitter_config.y, &properties, &mut self.rng);
                     self.entities.push(entity);
-                    next_id += 1;
                 }
             }
         }
@@ -77,6 +146,9 @@ impl SimulationEngine {
             field_ref.update(dt);
         }
         
+        // Spawn new entities so they are updated this frame
+        self.run_emitters(dt);
+        
         // Update entities in parallel
         let world_config = self.config.world.clone();
         let fields_ref = &self.fields;
@@ -93,28 +165,219 @@ impl SimulationEngine {
             }
         });
         
-        // Entity interactions - simplified approach
-        // For more complex simulations, you'd want spatial partitioning
-        // This is O(n²) and not efficient for large simulations
-        if self.entities.len() < 1000 {  // Only do interactions for smaller simulations
-            for i in 0..self.entities.len() {
-                for j in (i+1)..self.entities.len() {
-                    // Need safe way to get mutable refs to two elements
-                    let (left, right) = self.entities.split_at_mut(j);
-                    let entity1 = &mut left[i];
-                    let entity2 = &mut right[0];
-                    
-                    entity1.interact_with(entity2.as_mut());
-                    entity2.interact_with(entity1.as_mut());
-                }
+        // Remove entities that left the world
+        if let BoundaryMode::Kill = world_config.boundary_mode {
+            let bounds = world_config.bounds();
+            self.entities.retain(|entity| {
+                let (x, y) = entity.get_position();
+                bounds.contains(x, y)
+            });
+        }
+        
+        // Entity interactions, limited to nearby pairs found through the spatial grid
+        let max_range = self.entities.iter()
+            .map(|entity| entity.interaction_range())
+            .fold(0.0, f32::max);
+        if max_range > 0.0 {
+            self.spatial_grid.reset(2.0 * max_range);
+            for (index, entity) in self.entities.iter().enumerate() {
+                let (x, y) = entity.get_position();
+                self.spatial_grid.insert(index, x, y);
+            }
+            
+            let entities = &mut self.entities;
+            self.spatial_grid.for_each_candidate_pair(|i, j| {
+                // Need safe way to get mutable refs to two elements
+                let (left, right) = entities.split_at_mut(j);
+                let entity1 = &mut left[i];
+                let entity2 = &mut right[0];
+                
+                entity1.interact_with(entity2.as_mut());
+                entity2.interact_with(entity1.as_mut());
+            });
+        }
+    }
+    
+    pub fn serialize_state(&self, serializer: &dyn Serializer, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&serializer.serialize_entities(&self.entities));
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::config::{EmitterConfig, FieldConfig};
+    use crate::simulation::entity::particle::{Particle, ParticleFactory};
+    use std::collections::{HashMap, HashSet};
+    
+    // Tests share the global registry, so whichever test runs first does the registration
+    fn register_particles() {
+        let _ = REGISTRY.register_entity_factory("particle", Box::new(ParticleFactory));
+    }
+    
+    fn moving_particle(id: usize, x: f32, y: f32, vel_x: f32, vel_y: f32) -> Box<dyn Entity + Send + Sync> {
+        let mut particle = Particle::new(id, x as f64, y as f64, &mut StdRng::seed_from_u64(0));
+        particle.vel_x = vel_x;
+        particle.vel_y = vel_y;
+        particle.jitter = 0.0;
+        Box::new(particle)
+    }
+    
+    #[test]
+    fn kill_removes_entities_that_leave_the_world() {
+        let mut config = SimulationConfig::default();
+        config.world.width = 100.0;
+        config.world.height = 100.0;
+        config.world.boundary_mode = BoundaryMode::Kill;
+        let mut engine = SimulationEngine::new(4).with_config(config);
+        
+        engine.entities.push(moving_particle(0, 50.0, 50.0, 10.0, 0.0));
+        engine.entities.push(moving_particle(1, 95.0, 20.0, 10.0, 0.0));
+        engine.entities.push(moving_particle(2, 20.0, 5.0, 0.0, -10.0));
+        engine.update(1.0);
+        
+        let ids: Vec<u32> = engine.entities.iter().map(|entity| entity.get_id()).collect();
+        assert_eq!(ids, vec![0]);
+    }
+    
+    #[test]
+    fn emitter_spawns_rate_entities_per_simulated_second() {
+        register_particles();
+        let mut config = SimulationConfig::default();
+        config.entity_configs.clear();
+        config.emitter_configs.push(EmitterConfig {
+            entity_type: "particle".to_string(),
+            rate_per_sec: 50.0,
+            x: 3000.0,
+            y: 3000.0,
+            properties: HashMap::new(),
+        });
+        let mut engine = SimulationEngine::new(64).with_config(config);
+        engine.initialize().unwrap();
+        
+        for _ in 0..100 {
+            engine.update(0.01);
+        }
+        
+        // Accumulated float error may leave the last spawn a hair short of the second
+        let spawned = engine.entities.len();
+        assert!((49..=50).contains(&spawned), "spawned {}", spawned);
+    }
+    
+    #[test]
+    fn same_seed_gives_identical_runs() {
+        register_particles();
+        let run = || {
+            let mut config = SimulationConfig { seed: Some(42), ..SimulationConfig::default() };
+            config.emitter_configs.push(EmitterConfig {
+                entity_type: "particle".to_string(),
+                rate_per_sec: 100.0,
+                x: 3000.0,
+                y: 3000.0,
+                properties: HashMap::new(),
+            });
+            let mut engine = SimulationEngine::new(64).with_config(config);
+            engine.initialize().unwrap();
+            for _ in 0..30 {
+                engine.update(0.016);
             }
+            engine.entities.iter().map(|entity| entity.get_position()).collect::<Vec<_>>()
+        };
+        
+        assert_eq!(run(), run());
+    }
+    
+    #[test]
+    fn ids_are_unique_across_initialize_and_emitters() {
+        register_particles();
+        let mut config = SimulationConfig::default();
+        config.entity_configs[0].count = 20;
+        config.emitter_configs.push(EmitterConfig {
+            entity_type: "particle".to_string(),
+            rate_per_sec: 100.0,
+            x: 3000.0,
+            y: 3000.0,
+            properties: HashMap::new(),
+        });
+        let mut engine = SimulationEngine::new(64).with_config(config);
+        engine.initialize().unwrap();
+        for _ in 0..10 {
+            engine.update(0.05);
         }
+        
+        let ids: HashSet<u32> = engine.entities.iter().map(|entity| entity.get_id()).collect();
+        assert!(engine.entities.len() > 20);
+        assert_eq!(ids.len(), engine.entities.len());
+    }
+    
+    #[test]
+    fn initialize_rejects_unknown_types() {
+        let mut config = SimulationConfig::default();
+        config.entity_configs[0].entity_type = "no-such-entity".to_string();
+        let mut engine = SimulationEngine::new(64).with_config(config);
+        assert_eq!(engine.initialize(), Err(RegistryError::UnknownEntity("no-such-entity".to_string())));
+        
+        let mut config = SimulationConfig::default();
+        config.field_configs.push(FieldConfig {
+            field_type: "no-such-field".to_string(),
+            resolution: 4,
+            decay_rate: 0.0,
+            diffusion_rate: 0.0,
+            properties: HashMap::new(),
+        });
+        let mut engine = SimulationEngine::new(64).with_config(config);
+        assert_eq!(engine.initialize(), Err(RegistryError::UnknownField("no-such-field".to_string())));
+    }
+    
+    #[test]
+    fn same_seed_gives_identical_runs_with_interactions() {
+        register_particles();
+        // Crowded enough that particles collide every update, so pair order matters
+        let run = || {
+            let mut config = SimulationConfig { seed: Some(42), ..SimulationConfig::default() };
+            config.world.width = 300.0;
+            config.world.height = 300.0;
+            config.entity_configs[0].count = 2000;
+            let mut engine = SimulationEngine::new(64).with_config(config);
+            engine.initialize().unwrap();
+            for _ in 0..50 {
+                engine.update(0.016);
+            }
+            engine.entities.iter()
+                .map(|entity| {
+                    let (x, y) = entity.get_position();
+                    (x.to_bits(), y.to_bits())
+                })
+                .collect::<Vec<_>>()
+        };
+        
+        assert_eq!(run(), run());
     }
     
-    pub fn serialize_state(&self, buffer: &mut Vec<u8>) {
-        // Serialize entities
-        for entity in &self.entities {
-            buffer.extend_from_slice(&entity.serialize());
+    #[test]
+    fn emitter_without_spread_or_jitter_stays_finite() {
+        register_particles();
+        let mut config = SimulationConfig::default();
+        config.entity_configs.clear();
+        config.world.gravity.y = 50.0;
+        config.emitter_configs.push(EmitterConfig {
+            entity_type: "particle".to_string(),
+            rate_per_sec: 400.0,
+            x: 3000.0,
+            y: 3000.0,
+            properties: serde_json::from_value(serde_json::json!({ "velocity_spread": 0.0, "jitter": 0.0 })).unwrap(),
+        });
+        let mut engine = SimulationEngine::new(64).with_config(config);
+        engine.initialize().unwrap();
+        
+        // Every spawn lands on exactly the same point as the others from its tick
+        for _ in 0..10 {
+            engine.update(0.01);
+        }
+        
+        assert!(engine.entities.len() > 30);
+        for entity in &engine.entities {
+            let (x, y) = entity.get_position();
+            assert!(x.is_finite() && y.is_finite(), "entity {} at ({}, {})", entity.get_id(), x, y);
         }
     }
-}
\ No newline at end of file
+}
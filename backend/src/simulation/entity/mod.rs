@@ -6,6 +6,7 @@ use std::fmt::Debug;
 use std::sync::Arc;
 use crate::simulation::field::Field;
 use crate::simulation::config::WorldConfig;
+use rand::RngCore;
 
 // Backend - in entity/mod.rs or similar
 /// Binary serialization format for entities:
@@ -21,10 +22,18 @@ pub trait Entity: Send + Sync + Debug {
     fn update(&mut self, dt: f32, world: &WorldConfig, fields: &[Arc<dyn Field>]);
     fn interact_with(&mut self, other: &mut dyn Entity);
     
+    // Identity
+    fn get_id(&self) -> u32;
+    
     // Spatial methods
     fn get_position(&self) -> (f32, f32);
     fn get_radius(&self) -> f32;
     
+    // Entities are only paired for interact_with when closer than the sum of their interaction ranges
+    fn interaction_range(&self) -> f32 {
+        self.get_radius()
+    }
+    
     // Type information
     fn entity_type(&self) -> EntityType;
     fn as_any(&self) -> &dyn Any;
@@ -54,9 +63,13 @@ pub enum EntityType {
 }
 
 // Factory trait for creating entities
+// Any randomness must come from rng so that seeded runs are reproducible
 pub trait EntityFactory: Send + Sync {
-    fn create_entity(&self, id: u32, x: f32, y: f32, properties: &serde_json::Value) 
+    fn create_entity(&self, id: u32, x: f32, y: f32, properties: &serde_json::Value, rng: &mut dyn RngCore) 
         -> Box<dyn Entity>;
     fn entity_type(&self) -> EntityType;
     fn clone_factory(&self) -> Box<dyn EntityFactory>;
+    
+    // Property keys this factory reads; config validation rejects any others
+    fn known_properties(&self) -> &'static [&'static str];
 }
\ No newline at end of file
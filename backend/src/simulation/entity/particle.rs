@@ -4,7 +4,8 @@ use crate::simulation::config::{WorldConfig, BoundaryMode};
 use serde::{Serialize, Deserialize};
 use std::any::Any;
 use std::sync::Arc;
-use rand::Rng;
+use rand::{Rng, RngCore, SeedableRng};
+use rand::rngs::StdRng;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Particle {
@@ -12,11 +13,15 @@ pub struct Particle {
     pub vel_x: f32,
     pub vel_y: f32,
     pub max_speed: f32,
+    // Random velocity change per second
+    pub jitter: f32,
+    // Each particle draws from its own stream so parallel updates stay deterministic
+    #[serde(skip, default = "StdRng::from_entropy")]
+    rng: StdRng,
 }
 
 impl Particle {
-    pub fn new(id: usize, x: f64, y: f64) -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn new(id: usize, x: f64, y: f64, rng: &mut dyn RngCore) -> Self {
         Self {
             data: EntityData {
                 id: id as u32,
@@ -27,25 +32,32 @@ impl Particle {
             vel_x: rng.gen_range(-50.0..50.0),
             vel_y: rng.gen_range(-50.0..50.0),
             max_speed: 100.0,
+            jitter: 1000.0,
+            rng: StdRng::seed_from_u64(rng.next_u64()),
         }
     }
     
     // Enforce boundaries based on the boundary mode
     fn enforce_boundaries(&mut self, world: &WorldConfig) {
+        let bounds = world.bounds();
         match world.boundary_mode {
             BoundaryMode::Wrap => {
-                if self.data.pos_x < 0.0 { self.data.pos_x += world.width; }
-                if self.data.pos_x > world.width { self.data.pos_x -= world.width; }
-                if self.data.pos_y < 0.0 { self.data.pos_y += world.height; }
-                if self.data.pos_y > world.height { self.data.pos_y -= world.height; }
+                // rem_euclid also covers steps larger than the world itself
+                if self.data.pos_x < bounds.min_x || self.data.pos_x > bounds.max_x {
+                    self.data.pos_x = bounds.min_x + (self.data.pos_x - bounds.min_x).rem_euclid(bounds.width());
+                }
+                if self.data.pos_y < bounds.min_y || self.data.pos_y > bounds.max_y {
+                    self.data.pos_y = bounds.min_y + (self.data.pos_y - bounds.min_y).rem_euclid(bounds.height());
+                }
             },
             BoundaryMode::Bounce => {
-                if self.data.pos_x < 0.0 { self.data.pos_x = 0.0; self.vel_x = -self.vel_x; }
-                if self.data.pos_x > world.width { self.data.pos_x = world.width; self.vel_x = -self.vel_x; }
-                if self.data.pos_y < 0.0 { self.data.pos_y = 0.0; self.vel_y = -self.vel_y; }
-                if self.data.pos_y > world.height { self.data.pos_y = world.height; self.vel_y = -self.vel_y; }
+                let restitution = world.restitution;
+                if self.data.pos_x < bounds.min_x { self.data.pos_x = bounds.min_x; self.vel_x = -self.vel_x * restitution; }
+                if self.data.pos_x > bounds.max_x { self.data.pos_x = bounds.max_x; self.vel_x = -self.vel_x * restitution; }
+                if self.data.pos_y < bounds.min_y { self.data.pos_y = bounds.min_y; self.vel_y = -self.vel_y * restitution; }
+                if self.data.pos_y > bounds.max_y { self.data.pos_y = bounds.max_y; self.vel_y = -self.vel_y * restitution; }
             },
-            // Kill not implemented for particles
+            // Out-of-bounds particles are removed by the engine
             BoundaryMode::Kill => {},
         }
     }
@@ -53,23 +65,26 @@ impl Particle {
 
 impl Entity for Particle {
     fn update(&mut self, dt: f32, world: &WorldConfig, fields: &[Arc<dyn Field>]) {
-        // Random jitter
-        let mut rng = rand::thread_rng();
-        self.vel_x += rng.gen_range(-10.0..10.0);
-        self.vel_y += rng.gen_range(-10.0..10.0);
+        // Random jitter, scaled by dt so it doesn't depend on the tick rate
+        self.vel_x += self.rng.gen_range(-1.0..1.0) * self.jitter * dt;
+        self.vel_y += self.rng.gen_range(-1.0..1.0) * self.jitter * dt;
         
         // Apply field effects if any
         for field in fields {
             if field.field_type() == "scalar" {
-                if let FieldValue::Scalar(value) = field.get_value(self.data.pos_x, self.data.pos_y) {
-                    // Example: Scalar field pushes particles away from high values
-                    // This would be customized based on the specific field
-                    self.vel_x -= value * 10.0;
-                    self.vel_y -= value * 10.0;
+                if let FieldValue::Vector(grad_x, grad_y) = field.get_gradient(self.data.pos_x, self.data.pos_y) {
+                    // Scalar fields push particles down their gradient, away from high values.
+                    // This steers by the local slope rather than the raw value, so a uniform field has no effect.
+                    self.vel_x -= grad_x * 10.0;
+                    self.vel_y -= grad_y * 10.0;
                 }
             }
         }
         
+        // Global acceleration such as gravity
+        self.vel_x += world.gravity.x * dt;
+        self.vel_y += world.gravity.y * dt;
+        
         // Limit speed
         let speed = (self.vel_x * self.vel_x + self.vel_y * self.vel_y).sqrt();
         if speed > self.max_speed {
@@ -97,8 +112,14 @@ impl Entity for Particle {
             if dist_sq < min_dist * min_dist {
                 // Very simple elastic collision
                 let dist = dist_sq.sqrt();
-                let nx = dx / dist;
-                let ny = dy / dist;
+                let (nx, ny) = if dist > 0.0 {
+                    (dx / dist, dy / dist)
+                } else if self.data.id < other.get_id() {
+                    // Exactly on top of each other (e.g. spawned by the same emitter); split them along x by id
+                    (-1.0, 0.0)
+                } else {
+                    (1.0, 0.0)
+                };
                 
                 // Push away
                 self.vel_x += nx * 5.0;
@@ -107,6 +128,10 @@ impl Entity for Particle {
         }
     }
     
+    fn get_id(&self) -> u32 {
+        self.data.id
+    }
+    
     fn get_position(&self) -> (f32, f32) {
         (self.data.pos_x, self.data.pos_y)
     }
@@ -143,15 +168,35 @@ impl Entity for Particle {
 pub struct ParticleFactory;
 
 impl EntityFactory for ParticleFactory {
-    fn create_entity(&self, id: u32, x: f32, y: f32, properties: &serde_json::Value) -> Box<dyn Entity> {
+    fn create_entity(&self, id: u32, x: f32, y: f32, properties: &serde_json::Value, rng: &mut dyn RngCore) -> Box<dyn Entity> {
         // Parse properties if any
         let max_speed = properties.get("max_speed")
             .and_then(|v| v.as_f64())
             .map(|v| v as f32)
             .unwrap_or(100.0);
+        
+        let velocity_spread = properties.get("velocity_spread")
+            .and_then(|v| v.as_f64())
+            .map(|v| v as f32)
+            .unwrap_or(50.0);
+        
+        let jitter = properties.get("jitter")
+            .and_then(|v| v.as_f64())
+            .map(|v| v as f32)
+            .unwrap_or(1000.0);
             
-        let mut particle = Particle::new(id as usize, x as f64, y as f64);
+        let mut particle = Particle::new(id as usize, x as f64, y as f64, rng);
         particle.max_speed = max_speed;
+        particle.jitter = jitter;
+        
+        // Initial velocity is drawn uniformly from [-velocity_spread, velocity_spread) per axis
+        if velocity_spread.is_finite() && velocity_spread > 0.0 {
+            particle.vel_x = rng.gen_range(-velocity_spread..velocity_spread);
+            particle.vel_y = rng.gen_range(-velocity_spread..velocity_spread);
+        } else {
+            particle.vel_x = 0.0;
+            particle.vel_y = 0.0;
+        }
         
         Box::new(particle)
     }
@@ -163,4 +208,137 @@ impl EntityFactory for ParticleFactory {
     fn clone_factory(&self) -> Box<dyn EntityFactory> {
         Box::new(ParticleFactory)
     }
-}
\ No newline at end of file
+    
+    fn known_properties(&self) -> &'static [&'static str] {
+        &["max_speed", "velocity_spread", "jitter"]
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::config::GlobalForce;
+    
+    fn wrap_world() -> WorldConfig {
+        WorldConfig {
+            width: 100.0,
+            height: 50.0,
+            boundary_mode: BoundaryMode::Wrap,
+            ..WorldConfig::default()
+        }
+    }
+    
+    // A particle that moves exactly by its velocity each second
+    fn steady_particle(x: f32, y: f32, vel_x: f32, vel_y: f32) -> Particle {
+        let mut particle = Particle::new(0, x as f64, y as f64, &mut StdRng::seed_from_u64(0));
+        particle.vel_x = vel_x;
+        particle.vel_y = vel_y;
+        particle.jitter = 0.0;
+        particle.max_speed = f32::MAX;
+        particle
+    }
+    
+    #[test]
+    fn wrap_crosses_each_edge() {
+        let world = wrap_world();
+        let cases = [
+            // (start, velocity, expected end)
+            ((5.0, 25.0), (-10.0, 0.0), (95.0, 25.0)),
+            ((95.0, 25.0), (10.0, 0.0), (5.0, 25.0)),
+            ((50.0, 5.0), (0.0, -10.0), (50.0, 45.0)),
+            ((50.0, 45.0), (0.0, 10.0), (50.0, 5.0)),
+        ];
+        
+        for ((x, y), (vel_x, vel_y), (expected_x, expected_y)) in cases {
+            let mut particle = steady_particle(x, y, vel_x, vel_y);
+            particle.update(1.0, &world, &[]);
+            let (end_x, end_y) = particle.get_position();
+            assert!((end_x - expected_x).abs() < 1e-3, "x: {} != {}", end_x, expected_x);
+            assert!((end_y - expected_y).abs() < 1e-3, "y: {} != {}", end_y, expected_y);
+        }
+    }
+    
+    #[test]
+    fn wrap_handles_steps_of_several_world_sizes() {
+        let world = wrap_world();
+        let mut particle = steady_particle(10.0, 10.0, 320.0, -170.0);
+        particle.update(1.0, &world, &[]);
+        
+        let (x, y) = particle.get_position();
+        assert!((x - 30.0).abs() < 1e-3, "x: {}", x);
+        assert!((y - 40.0).abs() < 1e-3, "y: {}", y);
+    }
+    
+    #[test]
+    fn jitter_scales_with_dt() {
+        let world = WorldConfig::default();
+        let jittery = || {
+            let mut particle = steady_particle(3000.0, 3000.0, 0.0, 0.0);
+            particle.jitter = 1000.0;
+            particle
+        };
+        
+        // Same seed, so both particles draw the same random direction
+        let mut short_step = jittery();
+        let mut long_step = jittery();
+        short_step.update(0.01, &world, &[]);
+        long_step.update(0.02, &world, &[]);
+        
+        assert!(short_step.vel_x != 0.0 || short_step.vel_y != 0.0);
+        assert!((long_step.vel_x - 2.0 * short_step.vel_x).abs() < 1e-4);
+        assert!((long_step.vel_y - 2.0 * short_step.vel_y).abs() < 1e-4);
+    }
+    
+    #[test]
+    fn restitution_scales_the_normal_speed_on_bounce() {
+        let world = WorldConfig {
+            width: 100.0,
+            height: 100.0,
+            boundary_mode: BoundaryMode::Bounce,
+            restitution: 0.5,
+            ..WorldConfig::default()
+        };
+        let mut particle = steady_particle(95.0, 50.0, 10.0, 3.0);
+        particle.update(1.0, &world, &[]);
+        
+        assert_eq!(particle.get_position().0, 100.0);
+        assert_eq!(particle.vel_x, -5.0);
+        assert_eq!(particle.vel_y, 3.0);
+    }
+    
+    #[test]
+    fn gravity_accelerates_particles_downwards() {
+        // y grows downwards, so positive gravity pulls towards the bottom of the world
+        let world = WorldConfig {
+            gravity: GlobalForce { x: 0.0, y: 9.8 },
+            ..WorldConfig::default()
+        };
+        let mut particle = steady_particle(3000.0, 3000.0, 2.0, 0.0);
+        let mut last_dy = 0.0;
+        
+        for step in 1..=5 {
+            let (_, y) = particle.get_position();
+            particle.update(0.1, &world, &[]);
+            assert!((particle.vel_y - 0.98 * step as f32).abs() < 1e-4, "step {}: {}", step, particle.vel_y);
+            assert_eq!(particle.vel_x, 2.0);
+            
+            // Each step covers more ground than the last
+            let dy = particle.get_position().1 - y;
+            assert!(dy > last_dy, "step {}: moved {} after {}", step, dy, last_dy);
+            last_dy = dy;
+        }
+    }
+    
+    #[test]
+    fn coincident_particles_are_pushed_apart() {
+        let mut first = steady_particle(50.0, 50.0, 0.0, 0.0);
+        let mut second = steady_particle(50.0, 50.0, 0.0, 0.0);
+        second.data.id = 1;
+        
+        first.interact_with(&mut second);
+        second.interact_with(&mut first);
+        
+        assert!(first.vel_x < 0.0 && second.vel_x > 0.0);
+        assert_eq!(first.vel_x, -second.vel_x);
+        assert_eq!((first.vel_y, second.vel_y), (0.0, 0.0));
+    }
+}
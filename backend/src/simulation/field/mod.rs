@@ -8,6 +8,11 @@ pub trait Field: Send + Sync {
     // Get field value at a position
     fn get_value(&self, x: f32, y: f32) -> FieldValue;
     
+    // Get the direction of steepest increase at a position, as a FieldValue::Vector
+    fn get_gradient(&self, _x: f32, _y: f32) -> FieldValue {
+        FieldValue::Vector(0.0, 0.0)
+    }
+    
     // Modify field at a position
     fn add_value(&mut self, x: f32, y: f32, value: FieldValue);
     
@@ -36,4 +41,7 @@ pub trait FieldFactory: Send + Sync {
                     properties: &serde_json::Value) -> Box<dyn Field>;
     fn field_type(&self) -> &'static str;
     fn clone_factory(&self) -> Box<dyn FieldFactory>;
+    
+    // Property keys this factory reads; config validation rejects any others
+    fn known_properties(&self) -> &'static [&'static str];
 }
\ No newline at end of file
@@ -32,25 +32,50 @@ impl ScalarField {
         }
     }
     
-    fn cell_index(&self, x: f32, y: f32) -> usize {
-        let x_idx = (x / self.cell_width) as usize;
-        let y_idx = (y / self.cell_height) as usize;
-        let x_idx = x_idx.min(self.resolution - 1);
-        let y_idx = y_idx.min(self.resolution - 1);
-        y_idx * self.resolution + x_idx
+    // Bilinearly interpolate between cell centres, clamping at the edges of the grid
+    fn sample(&self, x: f32, y: f32) -> f32 {
+        let max_idx = (self.resolution - 1) as f32;
+        let gx = (x / self.cell_width - 0.5).clamp(0.0, max_idx);
+        let gy = (y / self.cell_height - 0.5).clamp(0.0, max_idx);
+        
+        let x0 = gx.floor() as usize;
+        let y0 = gy.floor() as usize;
+        let x1 = (x0 + 1).min(self.resolution - 1);
+        let y1 = (y0 + 1).min(self.resolution - 1);
+        let tx = gx - x0 as f32;
+        let ty = gy - y0 as f32;
+        
+        let top = self.values[y0 * self.resolution + x0] * (1.0 - tx) + self.values[y0 * self.resolution + x1] * tx;
+        let bottom = self.values[y1 * self.resolution + x0] * (1.0 - tx) + self.values[y1 * self.resolution + x1] * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+    
+    // Local gradient (per world unit) from central differences one cell either side
+    pub fn gradient(&self, x: f32, y: f32) -> (f32, f32) {
+        let dx = (self.sample(x + self.cell_width, y) - self.sample(x - self.cell_width, y)) / (2.0 * self.cell_width);
+        let dy = (self.sample(x, y + self.cell_height) - self.sample(x, y - self.cell_height)) / (2.0 * self.cell_height);
+        (dx, dy)
     }
 }
 
 impl Field for ScalarField {
+    // Particles steer by get_gradient, but the sampled value is still part of the Field contract
+    // for anything that reads concentrations directly
     fn get_value(&self, x: f32, y: f32) -> FieldValue {
-        let idx = self.cell_index(x, y);
-        FieldValue::Scalar(self.values[idx])
+        FieldValue::Scalar(self.sample(x, y))
+    }
+    
+    fn get_gradient(&self, x: f32, y: f32) -> FieldValue {
+        let (dx, dy) = self.gradient(x, y);
+        FieldValue::Vector(dx, dy)
     }
     
     fn add_value(&mut self, x: f32, y: f32, value: FieldValue) {
         if let FieldValue::Scalar(val) = value {
-            let idx = self.cell_index(x, y);
-            self.values[idx] += val;
+            // Deposits go into the single cell containing the point; only reads are interpolated
+            let x_idx = ((x / self.cell_width) as usize).min(self.resolution - 1);
+            let y_idx = ((y / self.cell_height) as usize).min(self.resolution - 1);
+            self.values[y_idx * self.resolution + x_idx] += val;
         }
     }
     
@@ -148,4 +173,58 @@ impl FieldFactory for ScalarFieldFactory {
     fn clone_factory(&self) -> Box<dyn FieldFactory> {
         Box::new(ScalarFieldFactory)
     }
-}
\ No newline at end of file
+    
+    fn known_properties(&self) -> &'static [&'static str] {
+        &["decay_rate", "diffusion_rate"]
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // 10x10 cells over a 100x100 world; each cell holds its column index, so the value rises 0.1 per unit in x
+    fn ramp() -> ScalarField {
+        let mut field = ScalarField::new(100.0, 100.0, 10, 0.0, 0.0);
+        for y in 0..10 {
+            for x in 0..10 {
+                field.values[y * 10 + x] = x as f32;
+            }
+        }
+        field
+    }
+    
+    #[test]
+    fn sample_interpolates_between_cell_centres() {
+        let field = ramp();
+        assert!((field.sample(15.0, 50.0) - 1.0).abs() < 1e-5);
+        assert!((field.sample(10.0, 50.0) - 0.5).abs() < 1e-5);
+        assert!((field.sample(42.5, 37.0) - 3.75).abs() < 1e-5);
+    }
+    
+    #[test]
+    fn sample_clamps_at_the_edges() {
+        let field = ramp();
+        assert_eq!(field.sample(0.0, 50.0), 0.0);
+        assert_eq!(field.sample(-20.0, 50.0), 0.0);
+        assert_eq!(field.sample(100.0, 50.0), 9.0);
+        assert_eq!(field.sample(250.0, 120.0), 9.0);
+    }
+    
+    #[test]
+    fn gradient_of_a_linear_ramp() {
+        let (dx, dy) = ramp().gradient(50.0, 50.0);
+        assert!((dx - 0.1).abs() < 1e-5, "dx: {}", dx);
+        assert!(dy.abs() < 1e-5, "dy: {}", dy);
+    }
+    
+    #[test]
+    fn add_value_deposits_into_the_containing_cell() {
+        let mut field = ScalarField::new(100.0, 100.0, 10, 0.0, 0.0);
+        field.add_value(15.0, 25.0, FieldValue::Scalar(2.0));
+        field.add_value(100.0, 100.0, FieldValue::Scalar(1.0));
+        
+        assert_eq!(field.values[2 * 10 + 1], 2.0);
+        assert_eq!(field.values[9 * 10 + 9], 1.0);
+        assert_eq!(field.values.iter().sum::<f32>(), 3.0);
+    }
+}
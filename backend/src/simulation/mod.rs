@@ -4,54 +4,155 @@ pub mod field;
 pub mod transport;
 pub mod config;
 pub mod registry;
+pub mod spatial;
 
 use crate::simulation::engine::SimulationEngine;
 use crate::simulation::entity::Entity;
-use crate::simulation::config::SimulationConfig;
-use crate::simulation::transport::{Transport, Serializer};
+use crate::simulation::config::{SimulationConfig, SerializerType};
+use crate::simulation::transport::{Transport, TransportHealth, Serializer};
 use crate::simulation::transport::websocket::{WebSocketTransport, BinarySerializer};
-use tokio::sync::broadcast;
+use crate::simulation::transport::serialisation::JsonSerializer;
+use tokio::sync::{broadcast, watch};
 use tokio::time::{Instant, Duration, interval};
 use std::sync::Arc;
 
+// How much the simulation prints; set from --log-level
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(LogLevel::Error),
+            "warn" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            _ => Err(format!("Unknown log level '{}', expected error, warn, info or debug", s)),
+        }
+    }
+}
+
+// Upper bound on fixed steps per tick, so a long stall can't snowball into ever longer ticks
+const MAX_CATCH_UP_STEPS: u32 = 8;
+
 // Initialize the registry with default components
 pub fn initialize_registry() {
     use crate::simulation::registry::REGISTRY;
     use crate::simulation::entity::particle::ParticleFactory;
     use crate::simulation::field::scalar_field::ScalarFieldFactory;
     
-    REGISTRY.register_entity_factory("particle", Box::new(ParticleFactory));
-    REGISTRY.register_field_factory("scalar", Box::new(ScalarFieldFactory));
+    REGISTRY.register_entity_factory("particle", Box::new(ParticleFactory))
+        .expect("Default entity factories should only be registered once");
+    REGISTRY.register_field_factory("scalar", Box::new(ScalarFieldFactory))
+        .expect("Default field factories should only be registered once");
+}
+
+// Add dt to the accumulator and take out as many whole steps as should run this tick
+fn fixed_steps(accumulator: &mut f32, dt: f32, step: f32) -> u32 {
+    *accumulator += dt;
+    
+    let mut steps = 0;
+    while *accumulator >= step && steps < MAX_CATCH_UP_STEPS {
+        *accumulator -= step;
+        steps += 1;
+    }
+    
+    // Drop the backlog instead of trying to catch up on it next tick
+    if steps == MAX_CATCH_UP_STEPS {
+        *accumulator = 0.0;
+    }
+    steps
+}
+
+// Cap a tick's fixed steps so the run stops after exactly max_frames engine updates
+fn limit_updates(steps: u32, update_count: u32, max_frames: Option<u32>) -> u32 {
+    max_frames.map_or(steps, |max_frames| steps.min(max_frames.saturating_sub(update_count)))
 }
 
-pub async fn simulation_loop(tx: broadcast::Sender<Vec<u8>>, config: SimulationConfig) {
+pub async fn simulation_loop(tx: broadcast::Sender<Vec<u8>>, config: SimulationConfig, shutdown: watch::Receiver<bool>, log_level: LogLevel) {
+    let mut transport = WebSocketTransport::new(tx.clone(), config.max_chunk_size);
+    run_simulation(&mut transport, &tx, config, shutdown, log_level).await;
+}
+
+// Run until a limit is reached or shutdown is signalled, then close the transport
+async fn run_simulation(
+    transport: &mut dyn Transport,
+    tx: &broadcast::Sender<Vec<u8>>,
+    config: SimulationConfig,
+    mut shutdown: watch::Receiver<bool>,
+    log_level: LogLevel,
+) {
     let mut engine = SimulationEngine::new(config.batch_size).with_config(config.clone());
     let mut timer = interval(Duration::from_millis(config.tick_rate_ms));
     let mut state_buf = Vec::with_capacity(10000 * 13); // Initial capacity
     
     // Initialize the engine with configured entities and fields
-    engine.initialize();
+    if let Err(e) = engine.initialize() {
+        eprintln!("Failed to initialize simulation: {}", e);
+        return;
+    }
     
-    // Create transport and serializer
-    let serializer = Box::new(BinarySerializer);
-    let transport = WebSocketTransport::new(tx.clone(), serializer, config.max_chunk_size);
+    // Create serializer
+    let serializer: Box<dyn Serializer> = match config.serializer {
+        SerializerType::Binary => Box::new(BinarySerializer),
+        SerializerType::Json => Box::new(JsonSerializer),
+    };
     
     let mut frame_count = 0;
-    let mut last_time = Instant::now();
+    let mut update_count = 0;
+    let start_time = Instant::now();
+    let mut last_time = start_time;
+    let mut accumulator = 0.0;
     
     loop {
-        timer.tick().await;
+        if config.max_frames.is_some_and(|max_frames| update_count >= max_frames) {
+            println!("Stopping simulation: reached max_frames ({} updates)", update_count);
+            break;
+        }
+        if config.max_duration_secs.is_some_and(|max_secs| start_time.elapsed().as_secs_f64() >= max_secs) {
+            println!("Stopping simulation: reached max_duration_secs ({:.2}s elapsed)", start_time.elapsed().as_secs_f64());
+            break;
+        }
+        
+        tokio::select! {
+            _ = timer.tick() => {}
+            _ = shutdown.changed() => {
+                println!("Stopping simulation: shutdown requested");
+                break;
+            }
+        }
         let now = Instant::now();
         let dt = (now - last_time).as_secs_f32();
         last_time = now;
 
         // Update simulation
-        engine.update(dt);
+        match config.fixed_timestep_ms {
+            Some(step_ms) => {
+                let step = step_ms as f32 / 1000.0;
+                let steps = limit_updates(fixed_steps(&mut accumulator, dt, step), update_count, config.max_frames);
+                for _ in 0..steps {
+                    engine.update(step);
+                }
+                update_count += steps;
+            }
+            None => {
+                engine.update(dt);
+                update_count += 1;
+            }
+        }
 
         // Broadcast state if needed
-        if frame_count % config.broadcast_rate == 0 && tx.receiver_count() > 0 {
+        if frame_count % config.broadcast_rate == 0 && transport.health() == TransportHealth::Ok {
             state_buf.clear();
-            engine.serialize_state(&mut state_buf);
+            engine.serialize_state(serializer.as_ref(), &mut state_buf);
             
             if !state_buf.is_empty() {
                 if let Err(e) = transport.send_state(&state_buf) {
@@ -61,7 +162,7 @@ pub async fn simulation_loop(tx: broadcast::Sender<Vec<u8>>, config: SimulationC
         }
         
         frame_count += 1;
-        if frame_count % 60 == 0 {
+        if frame_count % 60 == 0 && log_level >= LogLevel::Info {
             println!("FPS: {:.2}, Entities: {}, Connections: {}", 
                 1.0 / dt, 
                 engine.entities.len(),
@@ -69,4 +170,88 @@ pub async fn simulation_loop(tx: broadcast::Sender<Vec<u8>>, config: SimulationC
             );
         }
     }
-}
\ No newline at end of file
+    
+    if let Err(e) = transport.close() {
+        eprintln!("Error closing transport: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::registry::REGISTRY;
+    use crate::simulation::entity::particle::ParticleFactory;
+    
+    #[tokio::test]
+    async fn max_frames_stops_after_that_many_updates() {
+        let _ = REGISTRY.register_entity_factory("particle", Box::new(ParticleFactory));
+        let config = SimulationConfig {
+            tick_rate_ms: 1,
+            max_frames: Some(5),
+            ..SimulationConfig::default()
+        };
+        let (tx, mut rx) = broadcast::channel(16);
+        let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+        
+        tokio::time::timeout(Duration::from_secs(5), simulation_loop(tx, config, shutdown_rx, LogLevel::Info))
+            .await
+            .expect("simulation loop should stop on its own");
+        
+        // Every frame is broadcast in a single chunk, so one message per update
+        let mut frames = 0;
+        while rx.try_recv().is_ok() {
+            frames += 1;
+        }
+        assert_eq!(frames, 5);
+    }
+    
+    #[tokio::test]
+    async fn shutdown_signal_stops_the_loop_and_closes_the_transport() {
+        let _ = REGISTRY.register_entity_factory("particle", Box::new(ParticleFactory));
+        let config = SimulationConfig { tick_rate_ms: 1, ..SimulationConfig::default() };
+        let (tx, _rx) = broadcast::channel(16);
+        let mut transport = WebSocketTransport::new(tx.clone(), config.max_chunk_size);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        
+        let run = run_simulation(&mut transport, &tx, config, shutdown_rx, LogLevel::Info);
+        let stop = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            shutdown_tx.send(true).unwrap();
+        };
+        tokio::time::timeout(Duration::from_secs(5), async { tokio::join!(run, stop) })
+            .await
+            .expect("simulation loop should stop on shutdown");
+        
+        assert_eq!(transport.health(), TransportHealth::Failed);
+    }
+    
+    #[test]
+    fn fixed_steps_runs_one_step_per_step_sized_tick() {
+        let mut accumulator = 0.0;
+        assert_eq!(fixed_steps(&mut accumulator, 0.006, 0.01), 0);
+        assert_eq!(fixed_steps(&mut accumulator, 0.006, 0.01), 1);
+        assert!((accumulator - 0.002).abs() < 1e-6);
+    }
+    
+    #[test]
+    fn fixed_steps_catches_up_after_a_long_frame() {
+        let mut accumulator = 0.0;
+        assert_eq!(fixed_steps(&mut accumulator, 0.055, 0.01), 5);
+        assert!((accumulator - 0.005).abs() < 1e-6);
+    }
+    
+    #[test]
+    fn fixed_steps_drops_the_backlog_past_the_catch_up_limit() {
+        let mut accumulator = 0.0;
+        assert_eq!(fixed_steps(&mut accumulator, 1.0, 0.01), MAX_CATCH_UP_STEPS);
+        assert_eq!(accumulator, 0.0);
+    }
+    
+    #[test]
+    fn limit_updates_stops_fixed_steps_at_max_frames() {
+        assert_eq!(limit_updates(MAX_CATCH_UP_STEPS, 0, None), MAX_CATCH_UP_STEPS);
+        assert_eq!(limit_updates(3, 0, Some(5)), 3);
+        assert_eq!(limit_updates(3, 3, Some(5)), 2);
+        assert_eq!(limit_updates(3, 5, Some(5)), 0);
+    }
+}
@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt;
 use std::sync::{Arc, RwLock};
 use crate::simulation::entity::{EntityFactory, EntityType};
 use crate::simulation::field::FieldFactory;
@@ -7,6 +9,28 @@ use once_cell::sync::Lazy;
 // Global registry for entity and field factories
 pub static REGISTRY: Lazy<Registry> = Lazy::new(|| Registry::new());
 
+// Errors from registering or looking up factories
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    DuplicateEntity(String),
+    DuplicateField(String),
+    UnknownEntity(String),
+    UnknownField(String),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::DuplicateEntity(name) => write!(f, "entity type '{}' is already registered", name),
+            RegistryError::DuplicateField(name) => write!(f, "field type '{}' is already registered", name),
+            RegistryError::UnknownEntity(name) => write!(f, "no factory registered for entity type '{}'", name),
+            RegistryError::UnknownField(name) => write!(f, "no factory registered for field type '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
 pub struct Registry {
     entity_factories: RwLock<HashMap<String, Box<dyn EntityFactory>>>,
     field_factories: RwLock<HashMap<String, Box<dyn FieldFactory>>>,
@@ -20,9 +44,15 @@ impl Registry {
         }
     }
     
-    pub fn register_entity_factory(&self, name: &str, factory: Box<dyn EntityFactory>) {
+    pub fn register_entity_factory(&self, name: &str, factory: Box<dyn EntityFactory>) -> Result<(), RegistryError> {
         let mut factories = self.entity_factories.write().unwrap();
-        factories.insert(name.to_string(), factory);
+        match factories.entry(name.to_string()) {
+            Entry::Occupied(_) => Err(RegistryError::DuplicateEntity(name.to_string())),
+            Entry::Vacant(entry) => {
+                entry.insert(factory);
+                Ok(())
+            }
+        }
     }
     
     pub fn get_entity_factory(&self, name: &str) -> Option<Box<dyn EntityFactory>> {
@@ -30,13 +60,41 @@ impl Registry {
         factories.get(name).map(|f| f.clone_factory())
     }
     
-    pub fn register_field_factory(&self, name: &str, factory: Box<dyn FieldFactory>) {
+    pub fn register_field_factory(&self, name: &str, factory: Box<dyn FieldFactory>) -> Result<(), RegistryError> {
         let mut factories = self.field_factories.write().unwrap();
-        factories.insert(name.to_string(), factory);
+        match factories.entry(name.to_string()) {
+            Entry::Occupied(_) => Err(RegistryError::DuplicateField(name.to_string())),
+            Entry::Vacant(entry) => {
+                entry.insert(factory);
+                Ok(())
+            }
+        }
     }
     
     pub fn get_field_factory(&self, name: &str) -> Option<Box<dyn FieldFactory>> {
         let factories = self.field_factories.read().unwrap();
         factories.get(name).map(|f| f.clone_factory())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::entity::particle::ParticleFactory;
+    use crate::simulation::field::scalar_field::ScalarFieldFactory;
+    
+    #[test]
+    fn duplicate_registrations_are_rejected() {
+        let registry = Registry::new();
+        assert_eq!(registry.register_entity_factory("particle", Box::new(ParticleFactory)), Ok(()));
+        assert_eq!(
+            registry.register_entity_factory("particle", Box::new(ParticleFactory)),
+            Err(RegistryError::DuplicateEntity("particle".to_string())),
+        );
+        
+        assert_eq!(registry.register_field_factory("scalar", Box::new(ScalarFieldFactory)), Ok(()));
+        assert_eq!(
+            registry.register_field_factory("scalar", Box::new(ScalarFieldFactory)),
+            Err(RegistryError::DuplicateField("scalar".to_string())),
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+
+// Offsets of the neighbouring cells that pair with a cell: the cell itself plus half of its
+// eight neighbours, so every pair of adjacent cells is visited exactly once
+const FORWARD_NEIGHBOURS: [(i32, i32); 4] = [(1, 0), (1, 1), (0, 1), (-1, 1)];
+
+// Uniform grid over entity indices used to find interaction candidates.
+// With a cell size of at least the largest interaction distance, any two entities within
+// that distance of each other are in the same or adjacent cells.
+// Cells are kept in an ordered map so pairs come out in the same order on every run;
+// interactions are order-dependent, and seeded runs have to be reproducible.
+#[derive(Debug)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: BTreeMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: BTreeMap::new(),
+        }
+    }
+    
+    // Remove all indices and change the cell size
+    pub fn reset(&mut self, cell_size: f32) {
+        self.cell_size = cell_size;
+        self.cells.clear();
+    }
+    
+    fn cell_of(&self, x: f32, y: f32) -> (i32, i32) {
+        ((x / self.cell_size).floor() as i32, (y / self.cell_size).floor() as i32)
+    }
+    
+    pub fn insert(&mut self, index: usize, x: f32, y: f32) {
+        let cell = self.cell_of(x, y);
+        self.cells.entry(cell).or_default().push(index);
+    }
+    
+    // Call f once for every unordered pair of indices in the same or adjacent cells, as (lower, higher)
+    pub fn for_each_candidate_pair(&self, mut f: impl FnMut(usize, usize)) {
+        for (&(cx, cy), indices) in &self.cells {
+            for (a, &i) in indices.iter().enumerate() {
+                for &j in &indices[a + 1..] {
+                    f(i.min(j), i.max(j));
+                }
+            }
+            
+            for (dx, dy) in FORWARD_NEIGHBOURS {
+                if let Some(neighbours) = self.cells.get(&(cx + dx, cy + dy)) {
+                    for &i in indices {
+                        for &j in neighbours {
+                            f(i.min(j), i.max(j));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use std::collections::HashSet;
+    
+    const RANGE: f32 = 5.0;
+    
+    // Ten tight clusters scattered over a large world, like swarming particles
+    fn clustered_points() -> Vec<(f32, f32)> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut points = Vec::new();
+        for _ in 0..10 {
+            let (cx, cy) = (rng.gen_range(0.0..10_000.0), rng.gen_range(0.0..10_000.0));
+            for _ in 0..50 {
+                points.push((cx + rng.gen_range(-15.0..15.0), cy + rng.gen_range(-15.0..15.0)));
+            }
+        }
+        points
+    }
+    
+    fn candidate_pairs(points: &[(f32, f32)]) -> Vec<(usize, usize)> {
+        let mut grid = SpatialGrid::new(2.0 * RANGE);
+        for (index, &(x, y)) in points.iter().enumerate() {
+            grid.insert(index, x, y);
+        }
+        let mut pairs = Vec::new();
+        grid.for_each_candidate_pair(|i, j| pairs.push((i, j)));
+        pairs
+    }
+    
+    #[test]
+    fn candidates_are_unique_ordered_pairs() {
+        let pairs = candidate_pairs(&clustered_points());
+        let unique: HashSet<(usize, usize)> = pairs.iter().copied().collect();
+        assert_eq!(unique.len(), pairs.len());
+        assert!(pairs.iter().all(|&(i, j)| i < j));
+    }
+    
+    #[test]
+    fn candidates_include_every_pair_in_range() {
+        let points = clustered_points();
+        let pairs: HashSet<(usize, usize)> = candidate_pairs(&points).into_iter().collect();
+        
+        let mut in_range = 0;
+        for i in 0..points.len() {
+            for j in i + 1..points.len() {
+                let (dx, dy) = (points[i].0 - points[j].0, points[i].1 - points[j].1);
+                if (dx * dx + dy * dy).sqrt() < 2.0 * RANGE {
+                    in_range += 1;
+                    assert!(pairs.contains(&(i, j)), "missing pair ({}, {})", i, j);
+                }
+            }
+        }
+        assert!(in_range > 0);
+    }
+    
+    #[test]
+    fn clustered_points_need_far_fewer_candidates_than_brute_force() {
+        let points = clustered_points();
+        let brute_force = points.len() * (points.len() - 1) / 2;
+        assert!(candidate_pairs(&points).len() * 10 < brute_force);
+    }
+    
+    #[test]
+    fn pair_order_only_depends_on_the_points() {
+        let points = clustered_points();
+        assert_eq!(candidate_pairs(&points), candidate_pairs(&points));
+    }
+}
@@ -3,16 +3,34 @@ use std::error::Error;
 use std::fmt::Debug;
 
 pub mod websocket;
+pub mod serialisation;
+pub mod region;
 
 // Transport trait for sending simulation state to clients
 pub trait Transport: Send + Sync + Debug {
     fn init(&mut self) -> Result<(), Box<dyn Error>>;
     fn send_state(&self, state: &[u8]) -> Result<(), Box<dyn Error>>;
     fn close(&mut self) -> Result<(), Box<dyn Error>>;
+    
+    // Report whether sending is currently worthwhile, without waiting for a send to fail
+    fn health(&self) -> TransportHealth {
+        TransportHealth::Ok
+    }
+}
+
+// Health of a transport as reported by Transport::health
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportHealth {
+    // Ready to deliver state
+    Ok,
+    // Working, but sending would currently reach nobody (e.g. no connected clients)
+    Degraded,
+    // Broken; sends will not succeed
+    Failed,
 }
 
 // Serializer trait for different output formats
 pub trait Serializer: Send + Sync + Debug {
-    fn serialize_entities(&self, entities: &[Box<dyn crate::simulation::entity::Entity>]) -> Vec<u8>;
-    fn serialize_fields(&self, fields: &[std::sync::Arc<dyn crate::simulation::field::Field>]) -> Vec<u8>;
+    fn serialize_entities(&self, entities: &[Box<dyn crate::simulation::entity::Entity + Send + Sync>]) -> Vec<u8>;
+    fn serialize_fields(&self, fields: &[std::sync::Arc<dyn crate::simulation::field::Field + Send + Sync>]) -> Vec<u8>;
 }
\ No newline at end of file
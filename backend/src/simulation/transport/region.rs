@@ -0,0 +1,117 @@
+use crate::simulation::config::SerializerType;
+use serde::Deserialize;
+
+// Messages a client can send over its WebSocket, as JSON text frames:
+//   {"region": {"min_x": 0, "min_y": 0, "max_x": 500, "max_y": 500}}  only send entities inside the box
+//   {"region": null}                                                   send everything again
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientMessage {
+    pub region: Option<Region>,
+}
+
+// Axis-aligned box in world coordinates; edges are inclusive
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Region {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+// Size of one BinarySerializer record: type (u8), id (u32), x (f32), y (f32)
+const BINARY_RECORD_SIZE: usize = 13;
+
+impl Region {
+    pub fn is_valid(&self) -> bool {
+        [self.min_x, self.min_y, self.max_x, self.max_y].iter().all(|v| v.is_finite())
+            && self.min_x <= self.max_x
+            && self.min_y <= self.max_y
+    }
+    
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+    
+    // Keep only the entities of a serialized frame that lie inside the region
+    pub fn filter(&self, state: &[u8], serializer: &SerializerType) -> Vec<u8> {
+        match serializer {
+            SerializerType::Binary => self.filter_binary(state),
+            SerializerType::Json => self.filter_json(state),
+        }
+    }
+    
+    fn filter_binary(&self, state: &[u8]) -> Vec<u8> {
+        let position = |record: &[u8], offset: usize| f32::from_le_bytes(record[offset..offset + 4].try_into().unwrap());
+        state.chunks_exact(BINARY_RECORD_SIZE)
+            .filter(|record| self.contains(position(record, 5), position(record, 9)))
+            .flatten()
+            .copied()
+            .collect()
+    }
+    
+    fn filter_json(&self, state: &[u8]) -> Vec<u8> {
+        let Ok(mut records) = serde_json::from_slice::<Vec<serde_json::Value>>(state) else {
+            return state.to_vec();
+        };
+        records.retain(|record| {
+            match (record["x"].as_f64(), record["y"].as_f64()) {
+                (Some(x), Some(y)) => self.contains(x as f32, y as f32),
+                _ => false,
+            }
+        });
+        serde_json::to_vec(&records).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::entity::Entity;
+    use crate::simulation::entity::particle::Particle;
+    use crate::simulation::transport::Serializer;
+    use crate::simulation::transport::serialisation::JsonSerializer;
+    use crate::simulation::transport::websocket::BinarySerializer;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    
+    const REGION: Region = Region { min_x: 0.0, min_y: 0.0, max_x: 100.0, max_y: 100.0 };
+    
+    fn particles() -> Vec<Box<dyn Entity + Send + Sync>> {
+        let mut rng = StdRng::seed_from_u64(0);
+        [(10.0, 10.0), (150.0, 10.0), (100.0, 100.0), (50.0, -1.0)].iter().enumerate()
+            .map(|(id, &(x, y))| Box::new(Particle::new(id, x, y, &mut rng)) as Box<dyn Entity + Send + Sync>)
+            .collect()
+    }
+    
+    #[test]
+    fn binary_filter_keeps_records_inside() {
+        let state = BinarySerializer.serialize_entities(&particles());
+        let filtered = REGION.filter(&state, &SerializerType::Binary);
+        
+        let ids: Vec<u32> = filtered.chunks_exact(BINARY_RECORD_SIZE)
+            .map(|record| u32::from_le_bytes(record[1..5].try_into().unwrap()))
+            .collect();
+        assert_eq!(ids, vec![0, 2]);
+    }
+    
+    #[test]
+    fn json_filter_keeps_records_inside() {
+        let state = JsonSerializer.serialize_entities(&particles());
+        let filtered: serde_json::Value = serde_json::from_slice(&REGION.filter(&state, &SerializerType::Json)).unwrap();
+        
+        let ids: Vec<u64> = filtered.as_array().unwrap().iter().map(|record| record["id"].as_u64().unwrap()).collect();
+        assert_eq!(ids, vec![0, 2]);
+    }
+    
+    #[test]
+    fn client_messages_parse() {
+        let message: ClientMessage = serde_json::from_str(r#"{"region": {"min_x": 0, "min_y": 0, "max_x": 100, "max_y": 100}}"#).unwrap();
+        assert_eq!(message.region, Some(REGION));
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"region": null}"#).unwrap().region.is_none());
+        assert!(serde_json::from_str::<ClientMessage>(r#"{"regoin": null}"#).is_err());
+        assert!(REGION.is_valid());
+        assert!(!Region { min_x: 200.0, ..REGION }.is_valid());
+    }
+}
@@ -0,0 +1,66 @@
+use crate::simulation::transport::Serializer;
+use crate::simulation::entity::Entity;
+use crate::simulation::field::Field;
+use serde_json::json;
+use std::sync::Arc;
+
+// Human-readable serializer for debugging; the WebGL frontend only understands BinarySerializer output
+#[derive(Debug)]
+pub struct JsonSerializer;
+
+impl Serializer for JsonSerializer {
+    fn serialize_entities(&self, entities: &[Box<dyn Entity + Send + Sync>]) -> Vec<u8> {
+        // Format: [{"id", "entity_type", "x", "y", "radius"}, ...]
+        let records: Vec<serde_json::Value> = entities.iter().map(|entity| {
+            let (x, y) = entity.get_position();
+            json!({
+                "id": entity.get_id(),
+                "entity_type": entity.entity_type(),
+                "x": x,
+                "y": y,
+                "radius": entity.get_radius(),
+            })
+        }).collect();
+        
+        serde_json::to_vec(&records).unwrap_or_default()
+    }
+    
+    fn serialize_fields(&self, fields: &[Arc<dyn Field + Send + Sync>]) -> Vec<u8> {
+        // Fields only expose their own binary encoding, so it is passed through as a byte array
+        let records: Vec<serde_json::Value> = fields.iter().map(|field| {
+            json!({
+                "field_type": field.field_type(),
+                "data": field.serialize(),
+            })
+        }).collect();
+        
+        serde_json::to_vec(&records).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::entity::particle::Particle;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    
+    #[test]
+    fn json_entities_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let entities: Vec<Box<dyn Entity + Send + Sync>> = vec![
+            Box::new(Particle::new(7, 1.5, 2.5, &mut rng)),
+            Box::new(Particle::new(9, 300.0, 400.0, &mut rng)),
+        ];
+        
+        let records: serde_json::Value = serde_json::from_slice(&JsonSerializer.serialize_entities(&entities)).unwrap();
+        let records = records.as_array().unwrap();
+        assert_eq!(records.len(), 2);
+        
+        let parsed: Vec<(u64, f64, f64)> = records.iter()
+            .map(|record| (record["id"].as_u64().unwrap(), record["x"].as_f64().unwrap(), record["y"].as_f64().unwrap()))
+            .collect();
+        assert_eq!(parsed, vec![(7, 1.5, 2.5), (9, 300.0, 400.0)]);
+        assert_eq!(records[0]["entity_type"], "Particle");
+    }
+}
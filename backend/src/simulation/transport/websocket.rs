@@ -1,24 +1,93 @@
-use crate::simulation::transport::{Transport, Serializer};
+use crate::simulation::transport::{Transport, TransportHealth, Serializer};
 use crate::simulation::entity::Entity;
 use crate::simulation::field::Field;
 use tokio::sync::broadcast;
 use std::error::Error;
 use std::fmt::Debug;
+use std::num::TryFromIntError;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Chunk framing for state sent over the WebSocket:
+/// - bytes 0-3: Frame sequence number (u32, little-endian, wrapping)
+/// - bytes 4-7: Total payload size of the frame (u32, little-endian)
+/// - bytes 8-11: Chunk index within the frame (u32, little-endian)
+/// - bytes 12-15: Chunk count for the frame (u32, little-endian)
+/// - bytes 16..: Up to max_chunk_size bytes of payload
+///
+/// Concatenating the payloads of chunks 0..count of one sequence number gives the serialized frame.
+pub const CHUNK_HEADER_SIZE: usize = 16;
+
+// Split one serialized frame into header-prefixed chunks of at most max_chunk_size payload bytes
+pub fn frame_chunks(sequence: u32, state: &[u8], max_chunk_size: usize) -> Result<Vec<Vec<u8>>, TryFromIntError> {
+    let total_size = u32::try_from(state.len())?;
+    let chunk_count = u32::try_from(state.len().div_ceil(max_chunk_size))?;
+    
+    Ok(state.chunks(max_chunk_size).enumerate().map(|(chunk_index, chunk)| {
+        let mut message = Vec::with_capacity(CHUNK_HEADER_SIZE + chunk.len());
+        message.extend_from_slice(&sequence.to_le_bytes());
+        message.extend_from_slice(&total_size.to_le_bytes());
+        message.extend_from_slice(&(chunk_index as u32).to_le_bytes());
+        message.extend_from_slice(&chunk_count.to_le_bytes());
+        message.extend_from_slice(chunk);
+        message
+    }).collect())
+}
+
+// Reassembles frames from chunks in the order they were sent.
+// A frame with a missing or out-of-order chunk is dropped, and assembly restarts at the next chunk 0.
+#[derive(Debug, Default)]
+pub struct FrameAssembler {
+    sequence: u32,
+    next_index: u32,
+    buffer: Vec<u8>,
+}
+
+impl FrameAssembler {
+    // Add a chunk; returns the sequence number and payload once a frame is complete
+    pub fn push(&mut self, chunk: &[u8]) -> Option<(u32, Vec<u8>)> {
+        if chunk.len() < CHUNK_HEADER_SIZE {
+            return None;
+        }
+        let field = |offset: usize| u32::from_le_bytes(chunk[offset..offset + 4].try_into().unwrap());
+        let (sequence, index, count) = (field(0), field(8), field(12));
+        
+        if index == 0 {
+            self.sequence = sequence;
+            self.next_index = 0;
+            self.buffer.clear();
+        }
+        if sequence != self.sequence || index != self.next_index {
+            self.next_index = u32::MAX;
+            return None;
+        }
+        
+        self.buffer.extend_from_slice(&chunk[CHUNK_HEADER_SIZE..]);
+        self.next_index += 1;
+        if self.next_index == count {
+            self.next_index = u32::MAX;
+            Some((sequence, std::mem::take(&mut self.buffer)))
+        } else {
+            None
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct WebSocketTransport {
     tx: broadcast::Sender<Vec<u8>>,
-    serializer: Box<dyn Serializer>,
     max_chunk_size: usize,
+    sequence: AtomicU32,
+    closed: bool,
 }
 
 impl WebSocketTransport {
-    pub fn new(tx: broadcast::Sender<Vec<u8>>, serializer: Box<dyn Serializer>, max_chunk_size: usize) -> Self {
+    pub fn new(tx: broadcast::Sender<Vec<u8>>, max_chunk_size: usize) -> Self {
         Self {
             tx,
-            serializer,
             max_chunk_size,
+            sequence: AtomicU32::new(0),
+            closed: false,
         }
     }
 }
@@ -29,26 +98,43 @@ impl Transport for WebSocketTransport {
     }
     
     fn send_state(&self, state: &[u8]) -> Result<(), Box<dyn Error>> {
-        // Send in chunks if necessary
-        for chunk in state.chunks(self.max_chunk_size) {
+        if self.closed {
+            return Err("WebSocket transport is closed".into());
+        }
+        
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        
+        // Send in chunks, each prefixed with a header so the client can reassemble the frame
+        for message in frame_chunks(sequence, state, self.max_chunk_size)? {
             if self.tx.receiver_count() > 0 {
-                self.tx.send(chunk.to_vec())?;
+                self.tx.send(message)?;
             }
         }
         Ok(())
     }
     
     fn close(&mut self) -> Result<(), Box<dyn Error>> {
+        self.closed = true;
         Ok(())
     }
+    
+    fn health(&self) -> TransportHealth {
+        if self.closed {
+            TransportHealth::Failed
+        } else if self.tx.receiver_count() > 0 {
+            TransportHealth::Ok
+        } else {
+            TransportHealth::Degraded
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct BinarySerializer;
 
 impl Serializer for BinarySerializer {
-    fn serialize_entities(&self, entities: &[Box<dyn Entity>]) -> Vec<u8> {
-        let mut buffer = Vec::with_capacity(entities.len() * 12);
+    fn serialize_entities(&self, entities: &[Box<dyn Entity + Send + Sync>]) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(entities.len() * 13);
         
         for entity in entities {
             buffer.extend_from_slice(&entity.serialize());
@@ -57,7 +143,7 @@ impl Serializer for BinarySerializer {
         buffer
     }
     
-    fn serialize_fields(&self, fields: &[Arc<dyn Field>]) -> Vec<u8> {
+    fn serialize_fields(&self, fields: &[Arc<dyn Field + Send + Sync>]) -> Vec<u8> {
         let mut buffer = Vec::new();
         
         // Add field type identifier and data for each field
@@ -74,4 +160,69 @@ impl Serializer for BinarySerializer {
         
         buffer
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn health_follows_receivers_and_close() {
+        let (tx, _) = broadcast::channel(16);
+        let mut transport = WebSocketTransport::new(tx.clone(), 1024);
+        assert_eq!(transport.health(), TransportHealth::Degraded);
+        
+        let rx = tx.subscribe();
+        assert_eq!(transport.health(), TransportHealth::Ok);
+        
+        transport.close().unwrap();
+        assert_eq!(transport.health(), TransportHealth::Failed);
+        drop(rx);
+    }
+    
+    // Send one frame and return the (sequence, total, index, count) header and payload of each chunk
+    fn send_and_collect(transport: &WebSocketTransport, rx: &mut broadcast::Receiver<Vec<u8>>, state: &[u8]) -> Vec<([u32; 4], Vec<u8>)> {
+        transport.send_state(state).unwrap();
+        
+        let mut chunks = Vec::new();
+        while let Ok(message) = rx.try_recv() {
+            let mut header = [0u32; 4];
+            for (field, bytes) in header.iter_mut().zip(message[..CHUNK_HEADER_SIZE].chunks(4)) {
+                *field = u32::from_le_bytes(bytes.try_into().unwrap());
+            }
+            chunks.push((header, message[CHUNK_HEADER_SIZE..].to_vec()));
+        }
+        chunks
+    }
+    
+    fn reassemble(chunks: &[([u32; 4], Vec<u8>)]) -> Vec<u8> {
+        chunks.iter().flat_map(|(_, payload)| payload.iter().copied()).collect()
+    }
+    
+    #[test]
+    fn large_state_is_split_and_reassembles() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let transport = WebSocketTransport::new(tx, 100);
+        let state: Vec<u8> = (0..250u32).map(|i| i as u8).collect();
+        
+        let chunks = send_and_collect(&transport, &mut rx, &state);
+        let headers: Vec<[u32; 4]> = chunks.iter().map(|(header, _)| *header).collect();
+        assert_eq!(headers, vec![[0, 250, 0, 3], [0, 250, 1, 3], [0, 250, 2, 3]]);
+        assert_eq!(reassemble(&chunks), state);
+        
+        // The next frame gets the next sequence number
+        let chunks = send_and_collect(&transport, &mut rx, &state[..10]);
+        assert_eq!(chunks.iter().map(|(header, _)| *header).collect::<Vec<_>>(), vec![[1, 10, 0, 1]]);
+    }
+    
+    #[test]
+    fn exact_multiple_of_chunk_size_has_no_empty_trailing_chunk() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let transport = WebSocketTransport::new(tx, 100);
+        let state = vec![7u8; 200];
+        
+        let chunks = send_and_collect(&transport, &mut rx, &state);
+        let headers: Vec<[u32; 4]> = chunks.iter().map(|(header, _)| *header).collect();
+        assert_eq!(headers, vec![[0, 200, 0, 2], [0, 200, 1, 2]]);
+        assert_eq!(reassemble(&chunks), state);
+    }
+}
@@ -0,0 +1,43 @@
+use std::env;
+use std::fs;
+use std::process::Command;
+
+// Runs the real binary, so it needs port 3030 to be free
+#[test]
+fn runs_with_config_from_command_line() {
+    let path = env::temp_dir().join(format!("eusociety-cli-{}.json", std::process::id()));
+    fs::write(&path, r#"{
+        "tick_rate_ms": 1,
+        "broadcast_rate": 1,
+        "max_chunk_size": 65536,
+        "batch_size": 64,
+        "max_frames": 3,
+        "world": { "width": 100, "height": 100, "boundary_mode": "Wrap" },
+        "entity_configs": [{ "entity_type": "particle", "count": 10, "properties": {} }],
+        "field_configs": []
+    }"#).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_eusociety"))
+        .arg("--config")
+        .arg(&path)
+        .args(["--log-level", "warn"])
+        .output()
+        .unwrap();
+    let _ = fs::remove_file(&path);
+    
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "stdout: {}\nstderr: {}", stdout, String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains(&format!("Using configuration from: {}", path.display())), "{}", stdout);
+    assert!(stdout.contains("reached max_frames (3 updates)"), "{}", stdout);
+}
+
+#[test]
+fn rejects_unknown_flags() {
+    let output = Command::new(env!("CARGO_BIN_EXE_eusociety"))
+        .args(["--config", "unused.json", "--bogus"])
+        .output()
+        .unwrap();
+    
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown argument: --bogus"));
+}
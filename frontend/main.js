@@ -160,51 +160,68 @@
     const socket = new WebSocket("ws://127.0.0.1:3030");
     socket.binaryType = 'arraybuffer'; // Set binary data type
 
-    // Add these at the beginning of your code
-    let accumulatedChunks = [];
-    let accumulatedSize = 0;
+    // Chunk framing (see backend/src/simulation/transport/websocket.rs):
+    // [sequence:u32, totalSize:u32, chunkIndex:u32, chunkCount:u32, payload...], little-endian
+    const CHUNK_HEADER_SIZE = 16;
+    let currentSequence = null;
+    let frameBuffer = null;
+    let frameOffset = 0;
+    let chunksReceived = 0;
+
+    function processFrame(combined) {
+        // Create a view for the combined data
+        const view = new DataView(combined.buffer);
+        // Calculate the correct entity size - each entity has type(1) + id(4) + x(4) + y(4) = 13 bytes
+        numParticles = Math.floor(combined.length / 13);
+        
+        const flat = new Float32Array(numParticles * 2);
+        for (let i = 0; i < numParticles; i++) {
+            const offset = i * 13;
+            flat[i * 2] = view.getFloat32(offset + 5, true);     // X at offset 5
+            flat[i * 2 + 1] = view.getFloat32(offset + 9, true); // Y at offset 9
+        }
+
+        // Debug output
+        console.log(`Processed ${numParticles} particles from ${combined.length} bytes`);
+        
+        // Update WebGL buffer
+        gl.bindBuffer(gl.ARRAY_BUFFER, glBuffer);
+        gl.bufferData(gl.ARRAY_BUFFER, flat, gl.DYNAMIC_DRAW);
+    }
 
     // Single message handler
     socket.onmessage = async ({ data }) => {
         try {
             if (data instanceof ArrayBuffer) {
-                // Add new chunk to accumulated data
-                const chunk = new Uint8Array(data);
-                accumulatedChunks.push(chunk);
-                accumulatedSize += chunk.length;
-                
-                // Check if this is the last chunk (less than MAX_CHUNK_SIZE)
-                if (chunk.length < 65536) {
-                    // Combine all chunks
-                    const combined = new Uint8Array(accumulatedSize);
-                    let offset = 0;
-                    for (const chunk of accumulatedChunks) {
-                        combined.set(chunk, offset);
-                        offset += chunk.length;
-                    }
-                    
-                    // Create a view for the combined data
-                    const view = new DataView(combined.buffer);
-                    // Calculate the correct entity size - each entity has type(1) + id(4) + x(4) + y(4) = 13 bytes
-                    numParticles = Math.floor(combined.length / 13);
-                    
-                    const flat = new Float32Array(numParticles * 2);
-                    for (let i = 0; i < numParticles; i++) {
-                        const offset = i * 13;
-                        flat[i * 2] = view.getFloat32(offset + 5, true);     // X at offset 5
-                        flat[i * 2 + 1] = view.getFloat32(offset + 9, true); // Y at offset 9
-                    }
+                const header = new DataView(data, 0, CHUNK_HEADER_SIZE);
+                const sequence = header.getUint32(0, true);
+                const totalSize = header.getUint32(4, true);
+                const chunkIndex = header.getUint32(8, true);
+                const chunkCount = header.getUint32(12, true);
+                const payload = new Uint8Array(data, CHUNK_HEADER_SIZE);
+
+                // A new sequence number starts a new frame; any incomplete frame is dropped
+                if (sequence !== currentSequence) {
+                    currentSequence = sequence;
+                    frameBuffer = new Uint8Array(totalSize);
+                    frameOffset = 0;
+                    chunksReceived = 0;
+                }
+
+                // Chunks arrive in order, so a gap means part of this frame was lost
+                if (frameBuffer === null || chunkIndex !== chunksReceived) {
+                    frameBuffer = null;
+                    return;
+                }
+
+                frameBuffer.set(payload, frameOffset);
+                frameOffset += payload.length;
+                chunksReceived++;
 
-                    // Debug output
-                    console.log(`Processed ${numParticles} particles from ${accumulatedSize} bytes`);
-                    
-                    // Update WebGL buffer
-                    gl.bindBuffer(gl.ARRAY_BUFFER, glBuffer);
-                    gl.bufferData(gl.ARRAY_BUFFER, flat, gl.DYNAMIC_DRAW);
-                    
-                    // Reset for next message
-                    accumulatedChunks = [];
-                    accumulatedSize = 0;
+                if (chunksReceived === chunkCount) {
+                    processFrame(frameBuffer);
+                    currentSequence = null;
+                    frameBuffer = null;
                 }
             } else {
                 console.error('Received non-binary message');
@@ -213,8 +230,8 @@
             console.error('Error processing binary data:', err);
             console.error(err.stack);
             // Reset on error
-            accumulatedChunks = [];
-            accumulatedSize = 0;
+            currentSequence = null;
+            frameBuffer = null;
         }
     };
 